@@ -0,0 +1,142 @@
+// Fills one face of a cubemap with a full screen triangle: equirectangular conversion, mip levels and the IBL maps
+struct CubeFace {
+    face: u32,
+    // prefiltered mip level being rendered, 0 is a mirror
+    roughness: f32,
+    // face size of the sampled cubemap's top level
+    source_resolution: f32,
+    _padding: u32,
+}
+
+@group(0) @binding(0)
+var t_equirectangular: texture_2d<f32>;
+@group(0) @binding(1)
+var s_source: sampler;
+@group(0) @binding(2)
+var<uniform> cube_face: CubeFace;
+@group(0) @binding(3)
+var t_environment: texture_cube<f32>;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+    out.tex_coords = uv;
+    return out;
+}
+
+const PI: f32 = 3.14159265359;
+
+// the direction a cubemap lookup would need to land on this texel of the face
+fn face_direction(tex_coords: vec2<f32>) -> vec3<f32> {
+    let uv = tex_coords * 2.0 - 1.0;
+    switch cube_face.face {
+        case 0u: { return normalize(vec3<f32>(1.0, -uv.y, -uv.x)); }
+        case 1u: { return normalize(vec3<f32>(-1.0, -uv.y, uv.x)); }
+        case 2u: { return normalize(vec3<f32>(uv.x, 1.0, uv.y)); }
+        case 3u: { return normalize(vec3<f32>(uv.x, -1.0, -uv.y)); }
+        case 4u: { return normalize(vec3<f32>(uv.x, -uv.y, 1.0)); }
+        default: { return normalize(vec3<f32>(-uv.x, -uv.y, -1.0)); }
+    }
+}
+
+// any vector orthogonal to normal, used to build a tangent frame around it
+fn tangent_frame(normal: vec3<f32>) -> mat3x3<f32> {
+    var up = vec3<f32>(0.0, 1.0, 0.0);
+    if abs(normal.y) > 0.999 {
+        up = vec3<f32>(0.0, 0.0, 1.0);
+    }
+    let tangent = normalize(cross(up, normal));
+    let bitangent = cross(normal, tangent);
+    return mat3x3<f32>(tangent, bitangent, normal);
+}
+
+@fragment
+fn fs_equirectangular(in: VertexOutput) -> @location(0) vec4<f32> {
+    let direction = face_direction(in.tex_coords);
+    let uv = vec2<f32>(atan2(direction.z, direction.x) / (2.0 * PI) + 0.5, acos(clamp(direction.y, -1.0, 1.0)) / PI);
+    return vec4<f32>(textureSampleLevel(t_equirectangular, s_source, uv, 0.0).rgb, 1.0);
+}
+
+// the source is a view of the previous mip level
+@fragment
+fn fs_downsample(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(textureSampleLevel(t_environment, s_source, face_direction(in.tex_coords), 0.0).rgb, 1.0);
+}
+
+// cosine weighted integral over the hemisphere around the normal
+@fragment
+fn fs_irradiance(in: VertexOutput) -> @location(0) vec4<f32> {
+    let frame = tangent_frame(face_direction(in.tex_coords));
+    // a blurrier level keeps the sparse samples from picking up single bright texels
+    let level = max(log2(cube_face.source_resolution / 32.0), 0.0);
+
+    var irradiance = vec3<f32>(0.0);
+    var sample_count = 0.0;
+    let steps = 24;
+    for (var i = 0; i < steps * 4; i++) {
+        let phi = (f32(i) + 0.5) / f32(steps * 4) * 2.0 * PI;
+        for (var j = 0; j < steps; j++) {
+            let theta = (f32(j) + 0.5) / f32(steps) * 0.5 * PI;
+            let local = vec3<f32>(sin(theta) * cos(phi), sin(theta) * sin(phi), cos(theta));
+            let color = textureSampleLevel(t_environment, s_source, frame * local, level).rgb;
+            irradiance += color * cos(theta) * sin(theta);
+            sample_count += 1.0;
+        }
+    }
+    return vec4<f32>(PI * irradiance / sample_count, 1.0);
+}
+
+fn radical_inverse(index: u32) -> f32 {
+    var bits = index;
+    bits = (bits << 16u) | (bits >> 16u);
+    bits = ((bits & 0x55555555u) << 1u) | ((bits & 0xAAAAAAAAu) >> 1u);
+    bits = ((bits & 0x33333333u) << 2u) | ((bits & 0xCCCCCCCCu) >> 2u);
+    bits = ((bits & 0x0F0F0F0Fu) << 4u) | ((bits & 0xF0F0F0F0u) >> 4u);
+    bits = ((bits & 0x00FF00FFu) << 8u) | ((bits & 0xFF00FF00u) >> 8u);
+    return f32(bits) * 2.3283064365386963e-10;
+}
+
+fn importance_sample_ggx(xi: vec2<f32>, roughness: f32) -> vec3<f32> {
+    let a = roughness * roughness;
+    let phi = 2.0 * PI * xi.x;
+    let cos_theta = sqrt((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y));
+    let sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+    return vec3<f32>(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta);
+}
+
+// GGX importance sampling assuming the view direction equals the normal, the split sum approximation
+@fragment
+fn fs_prefilter(in: VertexOutput) -> @location(0) vec4<f32> {
+    let normal = face_direction(in.tex_coords);
+    let frame = tangent_frame(normal);
+    let roughness = max(cube_face.roughness, 0.02);
+    let a2 = roughness * roughness * roughness * roughness;
+    let texel_solid_angle = 4.0 * PI / (6.0 * cube_face.source_resolution * cube_face.source_resolution);
+
+    var color = vec3<f32>(0.0);
+    var total_weight = 0.0;
+    let sample_count = 64u;
+    for (var i = 0u; i < sample_count; i++) {
+        let half_dir = frame * importance_sample_ggx(vec2<f32>(f32(i) / f32(sample_count), radical_inverse(i)), roughness);
+        let light_dir = normalize(2.0 * dot(normal, half_dir) * half_dir - normal);
+        let n_dot_l = dot(normal, light_dir);
+        if n_dot_l > 0.0 {
+            // sampling a lower mip where samples are sparse avoids bright speckles
+            let n_dot_h = max(dot(normal, half_dir), 0.0);
+            let d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+            let pdf = a2 / (PI * d * d) / 4.0 + 0.0001;
+            let sample_solid_angle = 1.0 / (f32(sample_count) * pdf);
+            let level = max(0.5 * log2(sample_solid_angle / texel_solid_angle) + 1.0, 0.0);
+            color += textureSampleLevel(t_environment, s_source, light_dir, level).rgb * n_dot_l;
+            total_weight += n_dot_l;
+        }
+    }
+    return vec4<f32>(color / max(total_weight, 0.0001), 1.0);
+}
\ No newline at end of file
@@ -0,0 +1,38 @@
+// Drawn by the runner over the whole screen while a page is loading
+struct LoadingUniform {
+    resolution: vec2<f32>,
+    progress: f32,
+    time: f32,
+}
+@group(0) @binding(0)
+var<uniform> loading: LoadingUniform;
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    return vec4<f32>(uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+}
+
+// signed distance to a rounded rectangle centered on the origin
+fn rounded_box(p: vec2<f32>, half_size: vec2<f32>, radius: f32) -> f32 {
+    let q = abs(p) - half_size + radius;
+    return length(max(q, vec2<f32>(0.0))) + min(max(q.x, q.y), 0.0) - radius;
+}
+
+@fragment
+fn fs_main(@builtin(position) frag_coord: vec4<f32>) -> @location(0) vec4<f32> {
+    // same blue the pages clear to, so switching over doesn't flash
+    let background = vec3<f32>(0.012, 0.627, 1.0);
+    let p = frag_coord.xy - loading.resolution * 0.5;
+    let half_size = vec2<f32>(min(loading.resolution.x * 0.3, 320.0), max(loading.resolution.y * 0.006, 4.0));
+
+    let track = 1.0 - smoothstep(-0.5, 0.5, rounded_box(p, half_size, half_size.y));
+    let fill_edge = half_size.x * (2.0 * loading.progress - 1.0);
+    let fill = track * (1.0 - smoothstep(-0.5, 0.5, p.x - fill_edge));
+    // highlight sweeping across the bar so it keeps moving while the progress stalls
+    let shimmer = 0.5 + 0.5 * sin(p.x * 0.04 - loading.time * 4.0);
+
+    var color = mix(background, vec3<f32>(1.0), track * (0.25 + 0.1 * shimmer));
+    color = mix(color, vec3<f32>(1.0), fill * (0.8 + 0.2 * shimmer));
+    return vec4<f32>(color, 1.0);
+}
\ No newline at end of file
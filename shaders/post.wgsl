@@ -0,0 +1,163 @@
+// Full screen passes turning the HDR scene into the final frame: bloom, tonemapping, vignette and FXAA
+struct PostUniform {
+    exposure: f32,
+    bloom_threshold: f32,
+    bloom_intensity: f32,
+    vignette_strength: f32,
+    tonemapper: u32,
+    _padding0: u32,
+    _padding1: u32,
+    _padding2: u32,
+}
+
+@group(0) @binding(0)
+var t_source: texture_2d<f32>;
+@group(0) @binding(1)
+var s_source: sampler;
+@group(0) @binding(2)
+var<uniform> post: PostUniform;
+@group(0) @binding(3)
+var t_bloom: texture_2d<f32>;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+    out.tex_coords = uv;
+    return out;
+}
+
+fn sample_source(uv: vec2<f32>) -> vec3<f32> {
+    return textureSampleLevel(t_source, s_source, uv, 0.0).rgb;
+}
+
+fn source_texel() -> vec2<f32> {
+    return 1.0 / vec2<f32>(textureDimensions(t_source));
+}
+
+// dual filter downsample, the bilinear taps already average 2x2 texels each
+fn downsample(uv: vec2<f32>) -> vec3<f32> {
+    let o = source_texel() * 0.5;
+    var color = sample_source(uv) * 4.0;
+    color += sample_source(uv + vec2<f32>(-o.x, -o.y));
+    color += sample_source(uv + vec2<f32>(o.x, -o.y));
+    color += sample_source(uv + vec2<f32>(-o.x, o.y));
+    color += sample_source(uv + vec2<f32>(o.x, o.y));
+    return color / 8.0;
+}
+
+// keeps only what's brighter than the threshold, with a soft knee to avoid a hard cutoff
+@fragment
+fn fs_bloom_prefilter(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = downsample(in.tex_coords) * post.exposure;
+    let brightness = max(color.r, max(color.g, color.b));
+    let knee = post.bloom_threshold * 0.5;
+    var soft = clamp(brightness - post.bloom_threshold + knee, 0.0, 2.0 * knee);
+    soft = soft * soft / (4.0 * knee + 0.0001);
+    let contribution = max(soft, brightness - post.bloom_threshold) / max(brightness, 0.0001);
+    return vec4<f32>(color * contribution, 1.0);
+}
+
+@fragment
+fn fs_bloom_downsample(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(downsample(in.tex_coords), 1.0);
+}
+
+// added on top of the next larger level
+@fragment
+fn fs_bloom_upsample(in: VertexOutput) -> @location(0) vec4<f32> {
+    let o = source_texel() * 0.5;
+    let uv = in.tex_coords;
+    var color = sample_source(uv + vec2<f32>(-o.x * 2.0, 0.0));
+    color += sample_source(uv + vec2<f32>(o.x * 2.0, 0.0));
+    color += sample_source(uv + vec2<f32>(0.0, -o.y * 2.0));
+    color += sample_source(uv + vec2<f32>(0.0, o.y * 2.0));
+    color += sample_source(uv + vec2<f32>(-o.x, -o.y)) * 2.0;
+    color += sample_source(uv + vec2<f32>(o.x, -o.y)) * 2.0;
+    color += sample_source(uv + vec2<f32>(-o.x, o.y)) * 2.0;
+    color += sample_source(uv + vec2<f32>(o.x, o.y)) * 2.0;
+    return vec4<f32>(color / 12.0, 1.0);
+}
+
+fn reinhard(color: vec3<f32>) -> vec3<f32> {
+    return color / (1.0 + color);
+}
+
+// Narkowicz's fit of the ACES filmic curve
+fn aces(color: vec3<f32>) -> vec3<f32> {
+    let a = 2.51;
+    let b = 0.03;
+    let c = 2.43;
+    let d = 0.59;
+    let e = 0.14;
+    return clamp((color * (a * color + b)) / (color * (c * color + d) + e), vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+@fragment
+fn fs_composite(in: VertexOutput) -> @location(0) vec4<f32> {
+    var color = sample_source(in.tex_coords) * post.exposure;
+    color += textureSampleLevel(t_bloom, s_source, in.tex_coords, 0.0).rgb * post.bloom_intensity;
+
+    switch post.tonemapper {
+        case 1u: {
+            color = reinhard(color);
+        }
+        case 2u: {
+            color = aces(color);
+        }
+        default: {
+            color = clamp(color, vec3<f32>(0.0), vec3<f32>(1.0));
+        }
+    }
+
+    let distance_from_center = distance(in.tex_coords, vec2<f32>(0.5));
+    color *= 1.0 - post.vignette_strength * smoothstep(0.4, 0.8, distance_from_center);
+
+    // the target is sRGB, the hardware applies the gamma curve
+    return vec4<f32>(color, 1.0);
+}
+
+// edge detection runs on roughly perceptual values, the input is linear
+fn luma(color: vec3<f32>) -> f32 {
+    return sqrt(dot(color, vec3<f32>(0.299, 0.587, 0.114)));
+}
+
+@fragment
+fn fs_fxaa(in: VertexOutput) -> @location(0) vec4<f32> {
+    let span_max = 8.0;
+    let reduce_min = 1.0 / 128.0;
+    let reduce_mul = 1.0 / 8.0;
+
+    let texel = source_texel();
+    let uv = in.tex_coords;
+    let rgb_m = sample_source(uv);
+    let luma_nw = luma(sample_source(uv + vec2<f32>(-1.0, -1.0) * texel));
+    let luma_ne = luma(sample_source(uv + vec2<f32>(1.0, -1.0) * texel));
+    let luma_sw = luma(sample_source(uv + vec2<f32>(-1.0, 1.0) * texel));
+    let luma_se = luma(sample_source(uv + vec2<f32>(1.0, 1.0) * texel));
+    let luma_m = luma(rgb_m);
+    let luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+    let luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+
+    var dir = vec2<f32>(
+        -((luma_nw + luma_ne) - (luma_sw + luma_se)),
+        (luma_nw + luma_sw) - (luma_ne + luma_se),
+    );
+    let dir_reduce = max((luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * reduce_mul, reduce_min);
+    let rcp_dir_min = 1.0 / (min(abs(dir.x), abs(dir.y)) + dir_reduce);
+    dir = clamp(dir * rcp_dir_min, vec2<f32>(-span_max), vec2<f32>(span_max)) * texel;
+
+    let rgb_a = 0.5 * (sample_source(uv + dir * (1.0 / 3.0 - 0.5)) + sample_source(uv + dir * (2.0 / 3.0 - 0.5)));
+    let rgb_b = rgb_a * 0.5 + 0.25 * (sample_source(uv - dir * 0.5) + sample_source(uv + dir * 0.5));
+    let luma_b = luma(rgb_b);
+    if luma_b < luma_min || luma_b > luma_max {
+        return vec4<f32>(rgb_a, 1.0);
+    }
+    return vec4<f32>(rgb_b, 1.0);
+}
\ No newline at end of file
@@ -0,0 +1,66 @@
+// Depth only pass rendering the scene from a light's point of view
+struct ShadowUniform {
+    view_proj: mat4x4<f32>,
+};
+@group(0) @binding(0)
+var<uniform> shadow: ShadowUniform;
+
+// the models' camera bind group is bound for its joints, the camera itself isn't used
+const MAX_JOINTS: u32 = 64u;
+struct Joints {
+    matrices: array<mat4x4<f32>, MAX_JOINTS>,
+}
+@group(1) @binding(1)
+var<uniform> joints: Joints;
+
+// has to match MAX_MORPH_TARGETS in morph.rs, packed four to a vec4
+struct Morph {
+    weights: array<vec4<f32>, 2>,
+    count: u32,
+    vertex_count: u32,
+    texture_width: u32,
+}
+@group(2) @binding(0)
+var<uniform> morph: Morph;
+@group(2) @binding(1)
+var t_morph: texture_2d<f32>;
+
+fn morph_delta(vertex_index: u32, morph_target: u32, offset: u32) -> vec3<f32> {
+    let texel = (morph_target * morph.vertex_count + vertex_index) * 2u + offset;
+    return textureLoad(t_morph, vec2<u32>(texel % morph.texture_width, texel / morph.texture_width), 0).xyz;
+}
+
+struct VertexInput{
+    @location(0) position: vec3<f32>,
+    @location(12) joints: vec4<u32>,
+    @location(13) weights: vec4<f32>,
+}
+
+struct InstanceInput {
+    @location(5) model_matrix_0: vec4<f32>,
+    @location(6) model_matrix_1: vec4<f32>,
+    @location(7) model_matrix_2: vec4<f32>,
+    @location(8) model_matrix_3: vec4<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32, model: VertexInput, instance: InstanceInput) -> @builtin(position) vec4<f32> {
+    let model_matrix = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+    var morphed = model.position;
+    for (var i = 0u; i < morph.count; i += 1u) {
+        morphed += morph_delta(vertex_index, i, 0u) * morph.weights[i / 4u][i % 4u];
+    }
+    var position = vec4<f32>(morphed, 1.0);
+    if (dot(model.weights, vec4<f32>(1.0)) > 0.0) {
+        position = (joints.matrices[model.joints.x] * model.weights.x
+            + joints.matrices[model.joints.y] * model.weights.y
+            + joints.matrices[model.joints.z] * model.weights.z
+            + joints.matrices[model.joints.w] * model.weights.w) * position;
+    }
+    return shadow.view_proj * model_matrix * position;
+}
\ No newline at end of file
@@ -0,0 +1,35 @@
+// Full screen triangle on the far plane looking up the sky in the view direction
+struct SkyboxUniform {
+    // inverse of the view projection without the camera's translation
+    inverse_view_proj: mat4x4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> skybox: SkyboxUniform;
+@group(0) @binding(1)
+var t_skybox: texture_cube<f32>;
+@group(0) @binding(2)
+var s_skybox: sampler;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) direction: vec3<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    let clip = uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0);
+    let world = skybox.inverse_view_proj * vec4<f32>(clip, 1.0, 1.0);
+
+    var out: VertexOutput;
+    // z equal to w puts it at a depth of 1, only where nothing else was drawn
+    out.clip_position = vec4<f32>(clip, 1.0, 1.0);
+    out.direction = world.xyz / world.w;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(textureSampleLevel(t_skybox, s_skybox, normalize(in.direction), 0.0).rgb, 1.0);
+}
\ No newline at end of file
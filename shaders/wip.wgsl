@@ -6,12 +6,70 @@ struct CameraUniform {
 @group(1) @binding(0) // 1.
 var<uniform> camera: CameraUniform;
 
+// has to match MAX_JOINTS in animation.rs
+const MAX_JOINTS: u32 = 64u;
+struct Joints {
+    matrices: array<mat4x4<f32>, MAX_JOINTS>,
+}
+// identity matrices for models without a skeleton
+@group(1) @binding(1)
+var<uniform> joints: Joints;
+
+// has to match MAX_MORPH_TARGETS in morph.rs, packed four to a vec4
+struct Morph {
+    weights: array<vec4<f32>, 2>,
+    count: u32,
+    vertex_count: u32,
+    texture_width: u32,
+}
+@group(3) @binding(0)
+var<uniform> morph: Morph;
+// a position and then a normal delta per vertex, one target after the other
+@group(3) @binding(1)
+var t_morph: texture_2d<f32>;
+
+fn morph_delta(vertex_index: u32, morph_target: u32, offset: u32) -> vec3<f32> {
+    let texel = (morph_target * morph.vertex_count + vertex_index) * 2u + offset;
+    return textureLoad(t_morph, vec2<u32>(texel % morph.texture_width, texel / morph.texture_width), 0).xyz;
+}
+
+const MAX_LIGHTS: u32 = 16u;
+const LIGHT_POINT: u32 = 0u;
+const LIGHT_DIRECTIONAL: u32 = 1u;
+const LIGHT_SPOT: u32 = 2u;
+
 struct Light {
-    position: vec3<f32>,
-    color: vec3<f32>,
+    // w: kind
+    position: vec4<f32>,
+    // w: range
+    direction: vec4<f32>,
+    // w: intensity
+    color: vec4<f32>,
+    // x: cos inner angle, y: cos outer angle, z: shadow map layer or -1
+    cone: vec4<f32>,
+    view_proj: mat4x4<f32>,
+}
+struct Lights {
+    lights: array<Light, MAX_LIGHTS>,
+    count: u32,
+    shadow_bias: f32,
+    shadow_normal_bias: f32,
+    shadow_texel_size: f32,
+    environment_intensity: f32,
+    environment_max_lod: f32,
 }
 @group(2) @binding(0)
-var<uniform> light: Light;
+var<uniform> lights: Lights;
+@group(2) @binding(1)
+var t_shadow: texture_depth_2d_array;
+@group(2) @binding(2)
+var s_shadow: sampler_comparison;
+@group(2) @binding(3)
+var t_irradiance: texture_cube<f32>;
+@group(2) @binding(4)
+var t_prefiltered: texture_cube<f32>;
+@group(2) @binding(5)
+var s_environment: sampler;
 
 
 struct VertexInput{
@@ -19,7 +77,9 @@ struct VertexInput{
     @location(1) tex_coords: vec2<f32>,
     @location(2) normal: vec3<f32>,
     @location(3) tangent: vec3<f32>,
-    @location(4) bitangent: vec3<f32>
+    @location(4) bitangent: vec3<f32>,
+    @location(12) joints: vec4<u32>,
+    @location(13) weights: vec4<f32>,
 }
 
 struct InstanceInput {
@@ -36,13 +96,30 @@ struct InstanceInput {
 struct VertexOutput{
     @builtin(position) clip_position: vec4<f32>,
     @location(0) tex_coords: vec2<f32>,
-    @location(1) tangent_position: vec3<f32>,
-    @location(2) tangent_light_position: vec3<f32>,
-    @location(3) tangent_view_position: vec3<f32>
+    @location(1) world_position: vec3<f32>,
+    @location(2) world_normal: vec3<f32>,
+    @location(3) world_tangent: vec3<f32>,
+    @location(4) world_bitangent: vec3<f32>
+}
+
+// blend of the vertex's joints, vertices without weights aren't skinned
+fn skin_matrix(vertex_joints: vec4<u32>, weights: vec4<f32>) -> mat4x4<f32> {
+    if (dot(weights, vec4<f32>(1.0)) == 0.0) {
+        return mat4x4<f32>(
+            vec4<f32>(1.0, 0.0, 0.0, 0.0),
+            vec4<f32>(0.0, 1.0, 0.0, 0.0),
+            vec4<f32>(0.0, 0.0, 1.0, 0.0),
+            vec4<f32>(0.0, 0.0, 0.0, 1.0),
+        );
+    }
+    return joints.matrices[vertex_joints.x] * weights.x
+        + joints.matrices[vertex_joints.y] * weights.y
+        + joints.matrices[vertex_joints.z] * weights.z
+        + joints.matrices[vertex_joints.w] * weights.w;
 }
 
 @vertex
-fn vs_main(model: VertexInput, instance: InstanceInput) -> VertexOutput{
+fn vs_main(@builtin(vertex_index) vertex_index: u32, model: VertexInput, instance: InstanceInput) -> VertexOutput{
     var model_matrix = mat4x4<f32>(
         instance.model_matrix_0,
         instance.model_matrix_1,
@@ -54,58 +131,229 @@ fn vs_main(model: VertexInput, instance: InstanceInput) -> VertexOutput{
             instance.normal_matrix_1,
             instance.normal_matrix_2,
         );
+    // morph targets are applied before skinning
+    var position = model.position;
+    var normal = model.normal;
+    for (var i = 0u; i < morph.count; i += 1u) {
+        let weight = morph.weights[i / 4u][i % 4u];
+        position += morph_delta(vertex_index, i, 0u) * weight;
+        normal += morph_delta(vertex_index, i, 1u) * weight;
+    }
 
-    let world_normal = normalize(normal_matrix * model.normal);
-    let world_tangent = normalize(normal_matrix * model.tangent);
-    let world_bitangent = normalize(normal_matrix * model.bitangent);
-    let tangent_matrix = transpose(mat3x3<f32>(
-        world_tangent,
-        world_bitangent,
-        world_normal,
-    ));
+    let skin = skin_matrix(model.joints, model.weights);
+    // assumes joints aren't scaled unevenly
+    let skin_normal = mat3x3<f32>(skin[0].xyz, skin[1].xyz, skin[2].xyz);
 
-    let world_position: vec4<f32> = model_matrix * vec4<f32>(model.position, 1.0);
+    let world_position: vec4<f32> = model_matrix * skin * vec4<f32>(position, 1.0);
 
     var out: VertexOutput;
     out.clip_position = camera.view_proj * world_position;
     out.tex_coords = model.tex_coords;
-    out.tangent_position = tangent_matrix * world_position.xyz;
-    out.tangent_view_position = tangent_matrix * camera.view_pos.xyz;
-    out.tangent_light_position = tangent_matrix * light.position;
+    out.world_position = world_position.xyz;
+    out.world_normal = normal_matrix * skin_normal * normal;
+    out.world_tangent = normal_matrix * skin_normal * model.tangent;
+    out.world_bitangent = normal_matrix * skin_normal * model.bitangent;
     return out;
 }
 
 // Fragment shader
 
+struct Material {
+    base_color_factor: vec4<f32>,
+    emissive_factor: vec3<f32>,
+    metallic_factor: f32,
+    roughness_factor: f32,
+    occlusion_strength: f32,
+    normal_scale: f32,
+}
+
 @group(0) @binding(0)
-var t_diffuse: texture_2d<f32>;
+var t_base_color: texture_2d<f32>;
 @group(0) @binding(1)
-var s_diffuse: sampler;
+var s_base_color: sampler;
 
 @group(0) @binding(2)
 var t_normal: texture_2d<f32>;
 @group(0) @binding(3)
 var s_normal: sampler;
 
+@group(0) @binding(4)
+var t_metallic_roughness: texture_2d<f32>;
+@group(0) @binding(5)
+var s_metallic_roughness: sampler;
+
+@group(0) @binding(6)
+var t_occlusion: texture_2d<f32>;
+@group(0) @binding(7)
+var s_occlusion: sampler;
+
+@group(0) @binding(8)
+var t_emissive: texture_2d<f32>;
+@group(0) @binding(9)
+var s_emissive: sampler;
+
+@group(0) @binding(10)
+var<uniform> material: Material;
+
+const PI: f32 = 3.14159265359;
+
+// Trowbridge-Reitz GGX normal distribution
+fn distribution_ggx(n_dot_h: f32, roughness: f32) -> f32 {
+    let a = roughness * roughness;
+    let a2 = a * a;
+    let d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    return a2 / (PI * d * d);
+}
+
+fn geometry_schlick_ggx(n_dot_x: f32, roughness: f32) -> f32 {
+    let r = roughness + 1.0;
+    let k = (r * r) / 8.0;
+    return n_dot_x / (n_dot_x * (1.0 - k) + k);
+}
+
+fn fresnel_schlick(cos_theta: f32, f0: vec3<f32>) -> vec3<f32> {
+    return f0 + (1.0 - f0) * pow(clamp(1.0 - cos_theta, 0.0, 1.0), 5.0);
+}
+
+// rough surfaces reflect less at grazing angles, used for the ambient light where no half vector exists
+fn fresnel_schlick_roughness(cos_theta: f32, f0: vec3<f32>, roughness: f32) -> vec3<f32> {
+    return f0 + (max(vec3<f32>(1.0 - roughness), f0) - f0) * pow(clamp(1.0 - cos_theta, 0.0, 1.0), 5.0);
+}
+
+// Karis' analytic fit of the split sum BRDF lookup table, saves a texture on WebGL
+fn environment_brdf(f0: vec3<f32>, roughness: f32, n_dot_v: f32) -> vec3<f32> {
+    let c0 = vec4<f32>(-1.0, -0.0275, -0.572, 0.022);
+    let c1 = vec4<f32>(1.0, 0.0425, 1.04, -0.04);
+    let r = roughness * c0 + c1;
+    let a004 = min(r.x * r.x, exp2(-9.28 * n_dot_v)) * r.x + r.y;
+    let ab = vec2<f32>(-1.04, 1.04) * a004 + r.zw;
+    return f0 * ab.x + ab.y;
+}
+
+// image based lighting from the environment's irradiance and prefiltered maps
+fn ambient(normal: vec3<f32>, view_dir: vec3<f32>, albedo: vec3<f32>, metallic: f32, roughness: f32) -> vec3<f32> {
+    let n_dot_v = max(dot(normal, view_dir), 0.0001);
+    let f0 = mix(vec3<f32>(0.04), albedo, metallic);
+    let fresnel = fresnel_schlick_roughness(n_dot_v, f0, roughness);
+
+    let irradiance = textureSample(t_irradiance, s_environment, normal).rgb;
+    let diffuse = (vec3<f32>(1.0) - fresnel) * (1.0 - metallic) * irradiance * albedo;
+
+    let reflected = reflect(-view_dir, normal);
+    let prefiltered = textureSampleLevel(t_prefiltered, s_environment, reflected, roughness * lights.environment_max_lod).rgb;
+    let specular = prefiltered * environment_brdf(f0, roughness, n_dot_v);
+
+    return (diffuse + specular) * lights.environment_intensity;
+}
+
+// Cook-Torrance BRDF for a single light, already multiplied by n.l
+fn shade(normal: vec3<f32>, view_dir: vec3<f32>, light_dir: vec3<f32>, radiance: vec3<f32>, albedo: vec3<f32>, metallic: f32, roughness: f32) -> vec3<f32> {
+    let half_dir = normalize(view_dir + light_dir);
+    let n_dot_l = max(dot(normal, light_dir), 0.0);
+    let n_dot_v = max(dot(normal, view_dir), 0.0001);
+    let n_dot_h = max(dot(normal, half_dir), 0.0);
+
+    let f0 = mix(vec3<f32>(0.04), albedo, metallic);
+    let fresnel = fresnel_schlick(max(dot(half_dir, view_dir), 0.0), f0);
+    let distribution = distribution_ggx(n_dot_h, roughness);
+    let geometry = geometry_schlick_ggx(n_dot_v, roughness) * geometry_schlick_ggx(n_dot_l, roughness);
+
+    let specular = distribution * geometry * fresnel / (4.0 * n_dot_v * n_dot_l + 0.0001);
+    let diffuse = (vec3<f32>(1.0) - fresnel) * (1.0 - metallic) * albedo / PI;
+
+    return (diffuse + specular) * radiance * n_dot_l;
+}
+
+// smooth falloff reaching zero at the light's range, as recommended by KHR_lights_punctual
+fn range_attenuation(distance: f32, range: f32) -> f32 {
+    let inverse_square = 1.0 / max(distance * distance, 0.0001);
+    if range <= 0.0 {
+        return inverse_square;
+    }
+    let ratio = distance / range;
+    let window = clamp(1.0 - ratio * ratio * ratio * ratio, 0.0, 1.0);
+    return window * window * inverse_square;
+}
+
+// 3x3 percentage closer filtering on top of the hardware 2x2 comparison filter
+fn shadow_factor(light: Light, world_position: vec3<f32>, normal: vec3<f32>) -> f32 {
+    let layer = i32(light.cone.z);
+    if layer < 0 {
+        return 1.0;
+    }
+
+    let shadow_position = light.view_proj * vec4<f32>(world_position + normal * lights.shadow_normal_bias, 1.0);
+    if shadow_position.w <= 0.0 {
+        return 1.0;
+    }
+    let ndc = shadow_position.xyz / shadow_position.w;
+    let uv = ndc.xy * vec2<f32>(0.5, -0.5) + 0.5;
+    if any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0)) || ndc.z > 1.0 {
+        return 1.0;
+    }
+
+    let depth = ndc.z - lights.shadow_bias;
+    var visibility = 0.0;
+    for (var x = -1; x <= 1; x++) {
+        for (var y = -1; y <= 1; y++) {
+            let offset = vec2<f32>(f32(x), f32(y)) * lights.shadow_texel_size;
+            visibility += textureSampleCompareLevel(t_shadow, s_shadow, uv + offset, layer, depth);
+        }
+    }
+    return visibility / 9.0;
+}
+
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32>{
-    let object_color: vec4<f32> = textureSample(t_diffuse, s_diffuse, in.tex_coords);
-    let object_normal: vec4<f32> = textureSample(t_normal, s_normal, in.tex_coords);
+    let base_color = textureSample(t_base_color, s_base_color, in.tex_coords) * material.base_color_factor;
+    let object_normal = textureSample(t_normal, s_normal, in.tex_coords);
+    let metallic_roughness = textureSample(t_metallic_roughness, s_metallic_roughness, in.tex_coords);
+    let occlusion = textureSample(t_occlusion, s_occlusion, in.tex_coords).r;
+    let emissive = textureSample(t_emissive, s_emissive, in.tex_coords).rgb * material.emissive_factor;
 
-    let tangent_normal = object_normal.xyz * 2.0 - 1.0;
-    let light_dir = normalize(in.tangent_light_position - in.tangent_position);
-    let view_dir = normalize(in.tangent_view_position  - in.tangent_position);
+    let metallic = clamp(metallic_roughness.b * material.metallic_factor, 0.0, 1.0);
+    let roughness = clamp(metallic_roughness.g * material.roughness_factor, 0.04, 1.0);
+    let ambient_occlusion = mix(1.0, occlusion, material.occlusion_strength);
 
-    let ambient_strength = 0.0;
-    let ambient_color = light.color * ambient_strength;
+    // tangent space normal into world space
+    var tangent_normal = object_normal.xyz * 2.0 - 1.0;
+    tangent_normal = vec3<f32>(tangent_normal.xy * material.normal_scale, tangent_normal.z);
+    let tangent_matrix = mat3x3<f32>(
+        normalize(in.world_tangent),
+        normalize(in.world_bitangent),
+        normalize(in.world_normal),
+    );
+    let normal = normalize(tangent_matrix * tangent_normal);
 
-    let diffuse_strength = max(dot(tangent_normal, light_dir), 0.0);
-    let diffuse_color = light.color * diffuse_strength;
+    let view_dir = normalize(camera.view_pos.xyz - in.world_position);
 
-    let half_dir = normalize(view_dir + light_dir);
-    let specular_strength = pow(max(dot(tangent_normal, half_dir),0.0), 32.0);
-    let specular_color = specular_strength * light.color;
+    let ambient_color = ambient(normal, view_dir, base_color.rgb, metallic, roughness) * ambient_occlusion;
+
+    var light_color = vec3<f32>(0.0);
+    for (var i = 0u; i < min(lights.count, MAX_LIGHTS); i++) {
+        let light = lights.lights[i];
+        let kind = u32(light.position.w);
+
+        var light_dir: vec3<f32>;
+        var attenuation = 1.0;
+        if kind == LIGHT_DIRECTIONAL {
+            light_dir = -normalize(light.direction.xyz);
+        } else {
+            let to_light = light.position.xyz - in.world_position;
+            light_dir = normalize(to_light);
+            attenuation = range_attenuation(length(to_light), light.direction.w);
+            if kind == LIGHT_SPOT {
+                let cos_angle = dot(normalize(light.direction.xyz), -light_dir);
+                attenuation *= smoothstep(light.cone.y, light.cone.x, cos_angle);
+            }
+        }
+
+        attenuation *= shadow_factor(light, in.world_position, normalize(in.world_normal));
+
+        let radiance = light.color.rgb * light.color.w * attenuation;
+        light_color += shade(normal, view_dir, light_dir, radiance, base_color.rgb, metallic, roughness);
+    }
 
-    let result = (ambient_color + diffuse_color + specular_color) * object_color.xyz;
-    return vec4<f32>(result, object_color.a);
+    let result = ambient_color + light_color + emissive;
+    return vec4<f32>(result, base_color.a);
 }
\ No newline at end of file
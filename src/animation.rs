@@ -0,0 +1,341 @@
+use bytemuck::{Pod, Zeroable};
+use cgmath::{InnerSpace, Matrix4, Quaternion, SquareMatrix, Vector3, VectorSpace};
+use wgpu::util::DeviceExt;
+use crate::model::Instance;
+
+// has to match MAX_JOINTS in wip.wgsl and shadow.wgsl
+pub const MAX_JOINTS: usize = 64;
+
+pub struct Joint {
+    pub name: String,
+    pub parent: Option<usize>,
+    // local transform when no clip animates it
+    pub rest: Instance,
+}
+
+pub struct Skeleton {
+    pub joints: Vec<Joint>,
+    pub inverse_bind_matrices: Vec<Matrix4<f32>>,
+    // transform of whatever the root joints hang from
+    pub root: Matrix4<f32>,
+    // parents before their children
+    order: Vec<usize>,
+}
+
+impl Skeleton {
+    pub fn new(joints: Vec<Joint>, inverse_bind_matrices: Vec<Matrix4<f32>>, root: Matrix4<f32>) -> Self {
+        let depth = |mut joint: usize| {
+            let mut depth = 0;
+            while let Some(parent) = joints[joint].parent {
+                joint = parent;
+                depth += 1;
+            }
+            depth
+        };
+        let mut order: Vec<usize> = (0..joints.len()).collect();
+        order.sort_by_key(|&joint| depth(joint));
+        Self {
+            joints,
+            inverse_bind_matrices,
+            root,
+            order,
+        }
+    }
+
+    pub fn rest_pose(&self) -> Vec<Instance> {
+        self.joints.iter().map(|joint| joint.rest).collect()
+    }
+
+    // what the vertex shader multiplies bind pose vertices with
+    pub fn joint_matrices(&self, pose: &[Instance]) -> Vec<Matrix4<f32>> {
+        let mut global = vec![Matrix4::identity(); self.joints.len()];
+        for &joint in &self.order {
+            let parent = self.joints[joint].parent.map_or(self.root, |parent| global[parent]);
+            global[joint] = parent * pose[joint].to_matrix();
+        }
+        global.iter()
+            .zip(&self.inverse_bind_matrices)
+            .map(|(global, inverse_bind)| global * inverse_bind)
+            .collect()
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Interpolation {
+    Step,
+    Linear,
+}
+
+pub enum Keyframes {
+    Translation(Vec<Vector3<f32>>),
+    Rotation(Vec<Quaternion<f32>>),
+    Scale(Vec<Vector3<f32>>),
+}
+
+pub struct Channel {
+    pub joint: usize,
+    pub interpolation: Interpolation,
+    pub times: Vec<f32>,
+    pub keyframes: Keyframes,
+}
+
+// the two keyframes around time and how far along between them it is
+fn keys(times: &[f32], interpolation: Interpolation, time: f32) -> (usize, usize, f32) {
+    let next = times.partition_point(|&t| t <= time);
+    if next == 0 {
+        return (0, 0, 0.0);
+    }
+    if next == times.len() {
+        return (next - 1, next - 1, 0.0);
+    }
+    let previous = next - 1;
+    let t = match interpolation {
+        Interpolation::Step => 0.0,
+        Interpolation::Linear => (time - times[previous]) / (times[next] - times[previous]),
+    };
+    (previous, next, t)
+}
+
+impl Channel {
+    fn sample(&self, time: f32, joint: &mut Instance) {
+        let (previous, next, t) = keys(&self.times, self.interpolation, time);
+        match &self.keyframes {
+            Keyframes::Translation(values) => joint.position = values[previous].lerp(values[next], t),
+            Keyframes::Rotation(values) => joint.rotation = slerp(values[previous], values[next], t),
+            Keyframes::Scale(values) => joint.scale = values[previous].lerp(values[next], t),
+        }
+    }
+}
+
+// morph target weights of every mesh in a model, one set per keyframe
+pub struct WeightsChannel {
+    pub interpolation: Interpolation,
+    pub times: Vec<f32>,
+    pub weights: Vec<Vec<f32>>,
+}
+
+impl WeightsChannel {
+    fn sample(&self, time: f32, weights: &mut Vec<f32>) {
+        let (previous, next, t) = keys(&self.times, self.interpolation, time);
+        weights.clear();
+        weights.extend(self.weights[previous].iter().zip(&self.weights[next]).map(|(a, b)| a + (b - a) * t));
+    }
+}
+
+pub struct AnimationClip {
+    pub name: String,
+    pub duration: f32,
+    pub channels: Vec<Channel>,
+    pub weights: Option<WeightsChannel>,
+}
+
+impl AnimationClip {
+    // joints the clip doesn't animate keep whatever pose already holds
+    pub fn sample(&self, time: f32, pose: &mut [Instance]) {
+        for channel in &self.channels {
+            channel.sample(time, &mut pose[channel.joint]);
+        }
+    }
+
+    // weights stay as they are when the clip doesn't animate them
+    pub fn sample_weights(&self, time: f32, weights: &mut Vec<f32>) {
+        if let Some(channel) = &self.weights {
+            channel.sample(time, weights);
+        }
+    }
+}
+
+// along the shorter arc
+fn slerp(a: Quaternion<f32>, b: Quaternion<f32>, t: f32) -> Quaternion<f32> {
+    let b = if a.dot(b) < 0.0 { -b } else { b };
+    a.slerp(b, t).normalize()
+}
+
+#[derive(Debug, Copy, Clone)]
+struct PlayingClip {
+    clip: usize,
+    time: f32,
+    looping: bool,
+}
+
+impl PlayingClip {
+    fn advance(&mut self, dt: f32, duration: f32) {
+        self.time += dt;
+        if self.looping && duration > 0.0 {
+            self.time %= duration;
+        } else {
+            self.time = self.time.min(duration);
+        }
+    }
+}
+
+// Plays one clip of a model's animations at a time, fading from the previous one when asked to
+pub struct AnimationPlayer {
+    pub speed: f32,
+    current: Option<PlayingClip>,
+    previous: Option<PlayingClip>,
+    fade_duration: f32,
+    fade_time: f32,
+}
+
+impl Default for AnimationPlayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AnimationPlayer {
+    pub fn new() -> Self {
+        Self {
+            speed: 1.0,
+            current: None,
+            previous: None,
+            fade_duration: 0.0,
+            fade_time: 0.0,
+        }
+    }
+
+    // clip is an index into the model's animations
+    pub fn play(&mut self, clip: usize, looping: bool) {
+        self.current = Some(PlayingClip { clip, time: 0.0, looping });
+        self.previous = None;
+    }
+
+    // blends from the current pose to the new clip over duration seconds
+    pub fn crossfade(&mut self, clip: usize, duration: f32, looping: bool) {
+        if duration <= 0.0 {
+            return self.play(clip, looping);
+        }
+        self.previous = self.current.take();
+        self.current = Some(PlayingClip { clip, time: 0.0, looping });
+        self.fade_duration = duration;
+        self.fade_time = 0.0;
+    }
+
+    pub fn stop(&mut self) {
+        self.current = None;
+        self.previous = None;
+    }
+
+    pub fn current_clip(&self) -> Option<usize> {
+        self.current.map(|playing| playing.clip)
+    }
+
+    // true once a clip that doesn't loop has reached its end
+    pub fn is_finished(&self, clips: &[AnimationClip]) -> bool {
+        self.current.is_none_or(|playing| !playing.looping && playing.time >= clips[playing.clip].duration)
+    }
+
+    pub fn update(&mut self, dt: instant::Duration, clips: &[AnimationClip]) {
+        let dt = dt.as_secs_f32() * self.speed;
+        if let Some(current) = &mut self.current {
+            current.advance(dt, clips[current.clip].duration);
+        }
+        if let Some(previous) = &mut self.previous {
+            previous.advance(dt, clips[previous.clip].duration);
+            self.fade_time += dt;
+            if self.fade_time >= self.fade_duration {
+                self.previous = None;
+            }
+        }
+    }
+
+    pub fn pose(&self, skeleton: &Skeleton, clips: &[AnimationClip]) -> Vec<Instance> {
+        let sample = |playing: &PlayingClip| {
+            let mut pose = skeleton.rest_pose();
+            clips[playing.clip].sample(playing.time, &mut pose);
+            pose
+        };
+        let Some(current) = &self.current else {
+            return skeleton.rest_pose();
+        };
+        let pose = sample(current);
+        let Some(previous) = &self.previous else {
+            return pose;
+        };
+
+        let t = (self.fade_time / self.fade_duration).clamp(0.0, 1.0);
+        sample(previous).iter()
+            .zip(pose)
+            .map(|(from, to)| Instance {
+                position: from.position.lerp(to.position, t),
+                rotation: slerp(from.rotation, to.rotation, t),
+                scale: from.scale.lerp(to.scale, t),
+            })
+            .collect()
+    }
+
+    // the morph target weights of a model, starting from the defaults of its meshes
+    pub fn weights(&self, default_weights: &[f32], clips: &[AnimationClip]) -> Vec<f32> {
+        let sample = |playing: &PlayingClip| {
+            let mut weights = default_weights.to_vec();
+            clips[playing.clip].sample_weights(playing.time, &mut weights);
+            weights
+        };
+        let Some(current) = &self.current else {
+            return default_weights.to_vec();
+        };
+        let weights = sample(current);
+        let Some(previous) = &self.previous else {
+            return weights;
+        };
+
+        let t = (self.fade_time / self.fade_duration).clamp(0.0, 1.0);
+        sample(previous).iter()
+            .zip(weights)
+            .map(|(from, to)| from + (to - from) * t)
+            .collect()
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct JointsUniform {
+    matrices: [[[f32; 4]; 4]; MAX_JOINTS],
+}
+
+// Joint matrices of one posed skeleton, bound next to the camera in the camera's bind group
+pub struct Skin {
+    buffer: wgpu::Buffer,
+    pub bind_group: wgpu::BindGroup,
+}
+
+impl Skin {
+    // starts out with identity matrices, which leaves unskinned meshes as they are
+    pub fn new(device: &wgpu::Device, camera_bind_group_layout: &wgpu::BindGroupLayout, camera_buffer: &wgpu::Buffer, label: &str) -> Self {
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(label),
+            contents: bytemuck::cast_slice(&[JointsUniform { matrices: [Matrix4::identity().into(); MAX_JOINTS] }]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: camera_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: camera_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: buffer.as_entire_binding(),
+                },
+            ],
+            label: Some(label),
+        });
+        Self {
+            buffer,
+            bind_group,
+        }
+    }
+
+    pub fn update(&self, queue: &wgpu::Queue, joint_matrices: &[Matrix4<f32>]) {
+        if joint_matrices.len() > MAX_JOINTS {
+            log::warn!("skeleton has {} joints, only the first {} are used", joint_matrices.len(), MAX_JOINTS);
+        }
+        let matrices: Vec<[[f32; 4]; 4]> = joint_matrices.iter()
+            .take(MAX_JOINTS)
+            .map(|&matrix| matrix.into())
+            .collect();
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&matrices));
+    }
+}
\ No newline at end of file
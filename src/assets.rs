@@ -0,0 +1,205 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::Hash;
+use std::pin::Pin;
+use std::rc::{Rc, Weak};
+use std::task::{Context, Poll, Waker};
+use crate::model::{Instance, Model};
+use crate::texture::{MipmapGenerator, Texture};
+
+// reference counted, the GPU resources behind it are freed once the last handle is dropped
+pub type Handle<T> = Rc<T>;
+
+// every mesh of a gltf file with the instances placing it in the scene
+pub type GltfModels = Vec<(Handle<Model>, Vec<Instance>)>;
+
+type LoadResult<T> = Result<Handle<T>, Rc<anyhow::Error>>;
+
+struct PendingLoad<T> {
+    result: Option<LoadResult<T>>,
+    wakers: Vec<Waker>,
+}
+
+// resolves once the first request for the same asset finishes loading it
+struct WaitForLoad<T>(Rc<RefCell<PendingLoad<T>>>);
+
+impl<T> Future for WaitForLoad<T> {
+    type Output = LoadResult<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut pending = self.0.borrow_mut();
+        match &pending.result {
+            Some(result) => Poll::Ready(result.clone()),
+            None => {
+                pending.wakers.push(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+enum Entry<T> {
+    Loading(Rc<RefCell<PendingLoad<T>>>),
+    // only a weak reference so the cache alone doesn't keep anything alive
+    Loaded(Weak<T>),
+}
+
+struct Cache<K, T> {
+    entries: Rc<RefCell<HashMap<K, Entry<T>>>>,
+}
+
+// Publishes the outcome of a load to everyone waiting on it. Dropping it unfinished, because
+// the loading future was dropped, fails the waiting requests instead of leaving them hanging.
+struct LoadGuard<K: Eq + Hash, T> {
+    key: Option<K>,
+    entries: Rc<RefCell<HashMap<K, Entry<T>>>>,
+    pending: Rc<RefCell<PendingLoad<T>>>,
+}
+
+impl<K: Eq + Hash, T> LoadGuard<K, T> {
+    fn finish(mut self, result: LoadResult<T>) {
+        if let Some(key) = self.key.take() {
+            match &result {
+                Ok(handle) => {
+                    self.entries.borrow_mut().insert(key, Entry::Loaded(Rc::downgrade(handle)));
+                }
+                // failed loads are retried on the next request
+                Err(_) => {
+                    self.entries.borrow_mut().remove(&key);
+                }
+            }
+        }
+        let mut pending = self.pending.borrow_mut();
+        pending.result = Some(result);
+        pending.wakers.drain(..).for_each(Waker::wake);
+    }
+}
+
+impl<K: Eq + Hash, T> Drop for LoadGuard<K, T> {
+    fn drop(&mut self) {
+        if let Some(key) = self.key.take() {
+            self.entries.borrow_mut().remove(&key);
+            let mut pending = self.pending.borrow_mut();
+            pending.result = Some(Err(Rc::new(anyhow::anyhow!("load was cancelled"))));
+            pending.wakers.drain(..).for_each(Waker::wake);
+        }
+    }
+}
+
+impl<K: Eq + Hash + Clone + std::fmt::Debug, T> Cache<K, T> {
+    fn new() -> Self {
+        Self {
+            entries: Rc::new(RefCell::new(HashMap::new())),
+        }
+    }
+
+    async fn get_or_load<F: Future<Output = anyhow::Result<T>>>(&self, key: K, load: impl FnOnce() -> F) -> anyhow::Result<Handle<T>> {
+        let loading = match self.entries.borrow().get(&key) {
+            Some(Entry::Loaded(asset)) => match asset.upgrade() {
+                Some(handle) => return Ok(handle),
+                None => None,
+            },
+            Some(Entry::Loading(pending)) => Some(pending.clone()),
+            None => None,
+        };
+        if let Some(pending) = loading {
+            return WaitForLoad(pending).await
+                .map_err(|e| anyhow::anyhow!("loading {:?} failed: {:#}", key, e));
+        }
+
+        let pending = Rc::new(RefCell::new(PendingLoad {
+            result: None,
+            wakers: Vec::new(),
+        }));
+        {
+            let mut entries = self.entries.borrow_mut();
+            // forget assets that have been freed before adding a new one
+            entries.retain(|_, entry| !matches!(entry, Entry::Loaded(asset) if asset.strong_count() == 0));
+            entries.insert(key.clone(), Entry::Loading(pending.clone()));
+        }
+
+        let guard = LoadGuard {
+            key: Some(key),
+            entries: self.entries.clone(),
+            pending,
+        };
+        let result = load().await.map(Rc::new).map_err(Rc::new);
+        guard.finish(result.clone());
+        result.map_err(|e| anyhow::anyhow!("{:#}", e))
+    }
+}
+
+// Deduplicates loads of the same file, including ones that are still in flight,
+// so textures shared between materials and models shared between pages only exist once
+pub struct AssetManager {
+    textures: Cache<(String, bool), Texture>,
+    colors: Cache<([u8; 4], bool), Texture>,
+    models: Cache<String, Model>,
+    gltf_scenes: Cache<String, GltfModels>,
+    shaders: Cache<String, wgpu::ShaderModule>,
+    mipmaps: MipmapGenerator,
+}
+
+impl Default for AssetManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AssetManager {
+    pub fn new() -> Self {
+        Self {
+            textures: Cache::new(),
+            colors: Cache::new(),
+            models: Cache::new(),
+            gltf_scenes: Cache::new(),
+            shaders: Cache::new(),
+            mipmaps: MipmapGenerator::new(),
+        }
+    }
+
+    // shared by every texture loaded with a mip chain
+    pub fn mipmaps(&self) -> &MipmapGenerator {
+        &self.mipmaps
+    }
+
+    pub async fn texture(&self, file_name: &str, is_normal_map: bool, device: &wgpu::Device, queue: &wgpu::Queue) -> anyhow::Result<Handle<Texture>> {
+        self.textures.get_or_load((file_name.to_string(), is_normal_map), || {
+            crate::resources::load_texture(file_name, is_normal_map, &self.mipmaps, device, queue)
+        }).await
+    }
+
+    // single texel textures standing in for maps a material doesn't have
+    pub async fn color_texture(&self, color: [u8; 4], is_normal_map: bool, device: &wgpu::Device, queue: &wgpu::Queue) -> anyhow::Result<Handle<Texture>> {
+        self.colors.get_or_load((color, is_normal_map), || async move {
+            Texture::from_color(device, queue, color, &format!("color_{:?}", color), is_normal_map)
+        }).await
+    }
+
+    // materials are created against layout, identical layouts are interchangeable in wgpu
+    // so pages creating their own copy of the material layout can still share models
+    pub async fn model(&self, file_name: &str, device: &wgpu::Device, queue: &wgpu::Queue, layout: &wgpu::BindGroupLayout) -> anyhow::Result<Handle<Model>> {
+        self.models.get_or_load(file_name.to_string(), || {
+            crate::resources::load_model(file_name, self, device, queue, layout)
+        }).await
+    }
+
+    // the cache only holds on to the file while the returned handle is alive, not just its models
+    pub async fn gltf(&self, file_name: &str, device: &wgpu::Device, queue: &wgpu::Queue, layout: &wgpu::BindGroupLayout) -> anyhow::Result<Handle<GltfModels>> {
+        self.gltf_scenes.get_or_load(file_name.to_string(), || async move {
+            let gltf = crate::resources::load_gltf(file_name, self, device, queue, layout).await?;
+            Ok(gltf.into_iter().map(|(model, instances)| (Rc::new(model), instances)).collect())
+        }).await
+    }
+
+    pub async fn shader(&self, file_name: &str, device: &wgpu::Device) -> anyhow::Result<Handle<wgpu::ShaderModule>> {
+        self.shaders.get_or_load(file_name.to_string(), || async move {
+            let source = crate::resources::load_string(file_name).await?;
+            Ok(device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some(file_name),
+                source: wgpu::ShaderSource::Wgsl(source.into()),
+            }))
+        }).await
+    }
+}
\ No newline at end of file
@@ -0,0 +1,111 @@
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, Point3, Transform, Vector3};
+
+// Axis aligned bounding box
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Aabb {
+    pub min: Point3<f32>,
+    pub max: Point3<f32>,
+}
+
+impl Aabb {
+    // a box at the origin with no size when there are no points
+    pub fn from_points(points: impl IntoIterator<Item = Point3<f32>>) -> Self {
+        let mut points = points.into_iter();
+        let Some(first) = points.next() else {
+            return Self { min: Point3::origin(), max: Point3::origin() };
+        };
+        points.fold(Self { min: first, max: first }, |aabb, point| Self {
+            min: Point3::new(aabb.min.x.min(point.x), aabb.min.y.min(point.y), aabb.min.z.min(point.z)),
+            max: Point3::new(aabb.max.x.max(point.x), aabb.max.y.max(point.y), aabb.max.z.max(point.z)),
+        })
+    }
+
+    pub fn center(&self) -> Point3<f32> {
+        self.min.midpoint(self.max)
+    }
+
+    // half the size along each axis
+    pub fn extents(&self) -> Vector3<f32> {
+        (self.max - self.min) * 0.5
+    }
+
+    // padded by the same amount on every side
+    pub fn grow(&self, amount: f32) -> Self {
+        let amount = Vector3::new(amount, amount, amount);
+        Self {
+            min: self.min - amount,
+            max: self.max + amount,
+        }
+    }
+
+    // the box around the transformed box, it grows under rotation
+    pub fn transform(&self, matrix: &Matrix4<f32>) -> Self {
+        let center = matrix.transform_point(self.center());
+        let extents = self.extents();
+        let extents = Vector3::new(
+            matrix.x.x.abs() * extents.x + matrix.y.x.abs() * extents.y + matrix.z.x.abs() * extents.z,
+            matrix.x.y.abs() * extents.x + matrix.y.y.abs() * extents.y + matrix.z.y.abs() * extents.z,
+            matrix.x.z.abs() * extents.x + matrix.y.z.abs() * extents.y + matrix.z.z.abs() * extents.z,
+        );
+        Self {
+            min: center - extents,
+            max: center + extents,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct BoundingSphere {
+    pub center: Point3<f32>,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    // centered on the points' bounding box, so it's not the smallest possible sphere
+    pub fn from_points(points: &[Point3<f32>]) -> Self {
+        let center = Aabb::from_points(points.iter().copied()).center();
+        let radius = points.iter()
+            .map(|point| (point - center).magnitude())
+            .fold(0.0, f32::max);
+        Self { center, radius }
+    }
+
+    // non-uniform scale grows the radius by the largest axis
+    pub fn transform(&self, matrix: &Matrix4<f32>) -> Self {
+        let scale = matrix.x.truncate().magnitude()
+            .max(matrix.y.truncate().magnitude())
+            .max(matrix.z.truncate().magnitude());
+        Self {
+            center: matrix.transform_point(self.center),
+            radius: self.radius * scale,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{Deg, Vector3};
+
+    fn assert_near(a: Point3<f32>, b: Point3<f32>) {
+        assert!((a - b).magnitude() < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn rotated_aabb_grows_to_fit() {
+        let aabb = Aabb { min: Point3::new(-1.0, -2.0, -1.0), max: Point3::new(1.0, 2.0, 1.0) };
+        let matrix = Matrix4::from_translation(Vector3::new(5.0, 0.0, 0.0)) * Matrix4::from_angle_y(Deg(45.0));
+        let transformed = aabb.transform(&matrix);
+        let half_diagonal = 2.0f32.sqrt();
+        assert_near(transformed.min, Point3::new(5.0 - half_diagonal, -2.0, -half_diagonal));
+        assert_near(transformed.max, Point3::new(5.0 + half_diagonal, 2.0, half_diagonal));
+    }
+
+    #[test]
+    fn aabb_keeps_its_size_under_a_quarter_turn() {
+        let aabb = Aabb { min: Point3::new(0.0, 0.0, 0.0), max: Point3::new(2.0, 1.0, 1.0) };
+        let transformed = aabb.transform(&Matrix4::from_angle_z(Deg(90.0)));
+        assert_near(transformed.min, Point3::new(-1.0, 0.0, 0.0));
+        assert_near(transformed.max, Point3::new(0.0, 2.0, 1.0));
+    }
+}
\ No newline at end of file
@@ -6,16 +6,23 @@ use std::f32::consts::FRAC_PI_2;
 use bytemuck::{Pod, Zeroable};
 use winit::keyboard::KeyCode;
 
+// maps GL's -1..1 clip depth to wgpu's 0..1, the arguments are columns
 #[rustfmt::skip]
 pub const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
     1.0, 0.0, 0.0, 0.0,
     0.0, 1.0, 0.0, 0.0,
-    0.0, 0.0, 0.5, 0.5,
-    0.0, 0.0, 0.0, 1.0,
+    0.0, 0.0, 0.5, 0.0,
+    0.0, 0.0, 0.5, 1.0,
 );
 
 const SAFE_FRAC_PI_2: f32 = FRAC_PI_2 - 0.0001;
 
+pub trait CameraView {
+    fn position(&self) -> Point3<f32>;
+
+    fn calc_matrix(&self) -> Matrix4<f32>;
+}
+
 #[derive(Debug)]
 pub struct Camera {
     pub position: Point3<f32>,
@@ -40,8 +47,14 @@ impl Camera {
             pitch: pitch.into(),
         }
     }
+}
 
-    pub fn calc_matrix(&self) -> Matrix4<f32> {
+impl CameraView for Camera {
+    fn position(&self) -> Point3<f32> {
+        self.position
+    }
+
+    fn calc_matrix(&self) -> Matrix4<f32> {
         let (sin_pitch, cos_pitch) = self.pitch.0.sin_cos();
         let (sin_yaw, cos_yaw) = self.yaw.0.sin_cos();
 
@@ -57,6 +70,77 @@ impl Camera {
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct OrbitCamera {
+    pub target: Point3<f32>,
+    radius: f32,
+    yaw: Rad<f32>,
+    pitch: Rad<f32>,
+    min_radius: f32,
+    max_radius: f32,
+}
+
+impl OrbitCamera {
+    pub fn new<
+        T: Into<Point3<f32>>,
+        Y: Into<Rad<f32>>,
+        P: Into<Rad<f32>>
+    >(
+        target: T,
+        radius: f32,
+        yaw: Y,
+        pitch: P,
+        min_radius: f32,
+        max_radius: f32,
+    ) -> Self {
+        Self {
+            target: target.into(),
+            radius: radius.clamp(min_radius, max_radius),
+            yaw: yaw.into(),
+            pitch: pitch.into(),
+            min_radius,
+            max_radius,
+        }
+    }
+
+    pub fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    pub fn set_radius(&mut self, radius: f32) {
+        self.radius = radius.clamp(self.min_radius, self.max_radius);
+    }
+
+    // t = 0 gives self and t = 1 gives other, used to render in between two fixed updates
+    pub fn lerp(&self, other: &Self, t: f32) -> Self {
+        Self {
+            target: self.target + (other.target - self.target) * t,
+            radius: self.radius + (other.radius - self.radius) * t,
+            yaw: self.yaw + (other.yaw - self.yaw) * t,
+            pitch: self.pitch + (other.pitch - self.pitch) * t,
+            min_radius: other.min_radius,
+            max_radius: other.max_radius,
+        }
+    }
+
+    // unit vector pointing from the target towards the camera
+    fn offset_direction(&self) -> Vector3<f32> {
+        let (sin_pitch, cos_pitch) = self.pitch.0.sin_cos();
+        let (sin_yaw, cos_yaw) = self.yaw.0.sin_cos();
+        Vector3::new(cos_pitch * cos_yaw, sin_pitch, cos_pitch * sin_yaw)
+    }
+}
+
+impl CameraView for OrbitCamera {
+    fn position(&self) -> Point3<f32> {
+        self.target + self.offset_direction() * self.radius
+    }
+
+    fn calc_matrix(&self) -> Matrix4<f32> {
+        Matrix4::look_at_rh(self.position(), self.target, Vector3::unit_y())
+    }
+}
+
 pub struct Projection {
     aspect: f32,
     fovy: Rad<f32>,
@@ -201,6 +285,105 @@ impl CameraController {
     }
 }
 
+#[derive(Debug)]
+pub struct OrbitCameraController {
+    rotate_horizontal: f32,
+    rotate_vertical: f32,
+    pan_horizontal: f32,
+    pan_vertical: f32,
+    scroll: f32,
+    yaw_velocity: f32,
+    pitch_velocity: f32,
+    zoom_velocity: f32,
+    pan_velocity: Vector2<f32>,
+    sensitivity: f32,
+    zoom_speed: f32,
+    pan_speed: f32,
+    damping: f32,
+}
+
+impl OrbitCameraController {
+    pub fn new(sensitivity: f32, zoom_speed: f32, pan_speed: f32, damping: f32) -> Self {
+        Self {
+            rotate_horizontal: 0.0,
+            rotate_vertical: 0.0,
+            pan_horizontal: 0.0,
+            pan_vertical: 0.0,
+            scroll: 0.0,
+            yaw_velocity: 0.0,
+            pitch_velocity: 0.0,
+            zoom_velocity: 0.0,
+            pan_velocity: Vector2::zero(),
+            sensitivity,
+            zoom_speed,
+            pan_speed,
+            damping,
+        }
+    }
+
+    pub fn process_mouse(&mut self, mouse_dx: f64, mouse_dy: f64) {
+        self.rotate_horizontal += mouse_dx as f32;
+        self.rotate_vertical += mouse_dy as f32;
+    }
+
+    pub fn process_pan(&mut self, mouse_dx: f64, mouse_dy: f64) {
+        self.pan_horizontal += mouse_dx as f32;
+        self.pan_vertical += mouse_dy as f32;
+    }
+
+    pub fn process_scroll(&mut self, delta: &MouseScrollDelta) {
+        self.scroll += -match delta {
+            MouseScrollDelta::LineDelta(_, scroll) => scroll * 100.0,
+            MouseScrollDelta::PixelDelta(PhysicalPosition {
+                                             y: scroll,
+                                             ..
+                                         }) => *scroll as f32
+        };
+    }
+
+    pub fn update_camera(&mut self, camera: &mut OrbitCamera, dt: Duration) {
+        let dt = dt.as_secs_f32();
+
+        // input gives the camera a push, damping slows it back down
+        self.yaw_velocity -= self.rotate_horizontal * self.sensitivity;
+        self.pitch_velocity += self.rotate_vertical * self.sensitivity;
+        self.zoom_velocity += self.scroll * self.zoom_speed;
+        self.pan_velocity += Vector2::new(-self.pan_horizontal, self.pan_vertical) * self.pan_speed;
+        self.rotate_horizontal = 0.0;
+        self.rotate_vertical = 0.0;
+        self.pan_horizontal = 0.0;
+        self.pan_vertical = 0.0;
+        self.scroll = 0.0;
+
+        // rotate
+        camera.yaw += Rad(self.yaw_velocity * dt);
+        camera.pitch += Rad(self.pitch_velocity * dt);
+        if camera.pitch.0.abs() > SAFE_FRAC_PI_2 {
+            camera.pitch = Rad(SAFE_FRAC_PI_2.copysign(camera.pitch.0));
+            self.pitch_velocity = 0.0;
+        }
+
+        // zoom, scaled by the current distance so it feels the same close up and far away
+        camera.set_radius(camera.radius * (self.zoom_velocity * dt).exp());
+        if camera.radius == camera.min_radius || camera.radius == camera.max_radius {
+            self.zoom_velocity = 0.0;
+        }
+
+        // pan the target in the view plane
+        let forward = -camera.offset_direction();
+        let right = forward.cross(Vector3::unit_y()).normalize();
+        let up = right.cross(forward);
+        camera.target += (right * self.pan_velocity.x + up * self.pan_velocity.y) * camera.radius * dt;
+
+        // inertia
+        let decay = (-self.damping * dt).exp();
+        self.yaw_velocity *= decay;
+        self.pitch_velocity *= decay;
+        self.zoom_velocity *= decay;
+        self.pan_velocity *= decay;
+    }
+}
+
 #[repr(C)]
 #[derive(Debug, Copy, Clone, Pod, Zeroable)]
 pub struct CameraUniform {
@@ -208,6 +391,12 @@ pub struct CameraUniform {
     view_proj: [[f32; 4]; 4],
 }
 
+impl Default for CameraUniform {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl CameraUniform {
     pub fn new() -> Self {
         use cgmath::SquareMatrix;
@@ -217,8 +406,8 @@ impl CameraUniform {
         }
     }
 
-    pub fn update_view_proj(&mut self, camera: &Camera, projection: &Projection) {
-        self.view_position = camera.position.to_homogeneous().into();
+    pub fn update_view_proj<C: CameraView>(&mut self, camera: &C, projection: &Projection) {
+        self.view_position = camera.position().to_homogeneous().into();
         self.view_proj = (projection.calc_matrix() * camera.calc_matrix()).into();
     }
 }
\ No newline at end of file
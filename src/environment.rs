@@ -0,0 +1,80 @@
+use anyhow::bail;
+use crate::grapics_context::GraphicsContext;
+use crate::texture::{CubePass, CubemapRenderer, Texture};
+
+const IRRADIANCE_SIZE: u32 = 32;
+const PREFILTERED_SIZE: u32 = 128;
+// roughness goes from 0 at the top level to 1 at the last one
+const PREFILTERED_MIP_LEVELS: u32 = 5;
+const MAX_SKYBOX_SIZE: u32 = 1024;
+
+// The sky around the scene and the maps materials use for image based ambient lighting
+pub struct Environment {
+    pub skybox: Texture,
+    // diffuse light arriving from the hemisphere around a normal
+    pub irradiance: Texture,
+    // the sky blurred for increasing roughness in each mip level, sampled in the reflected direction
+    pub prefiltered: Texture,
+    pub intensity: f32,
+}
+
+impl Environment {
+    // the maps are rendered, so this needs HDR render targets, skybox should have mipmaps to avoid speckles.
+    // renderer can be the one skybox was created with
+    pub fn from_cubemap(graphics_context: &GraphicsContext, renderer: &CubemapRenderer, skybox: Texture, intensity: f32) -> anyhow::Result<Self> {
+        if graphics_context.hdr_format() != Texture::HDR_FORMAT {
+            bail!("{:?} render targets are needed for image based lighting", Texture::HDR_FORMAT);
+        }
+        let device = &graphics_context.device;
+        let queue = &graphics_context.queue;
+
+        let irradiance = Texture::create_cubemap(device, IRRADIANCE_SIZE, 1, "irradiance_map");
+        let prefiltered = Texture::create_cubemap(device, PREFILTERED_SIZE, PREFILTERED_MIP_LEVELS, "prefiltered_map");
+        let size = skybox.texture.width();
+
+        renderer.render(device, queue, &irradiance, 0, CubePass::Irradiance(&skybox.view, size));
+        for mip in 0..PREFILTERED_MIP_LEVELS {
+            let roughness = mip as f32 / (PREFILTERED_MIP_LEVELS - 1) as f32;
+            renderer.render(device, queue, &prefiltered, mip, CubePass::Prefilter(&skybox.view, size, roughness));
+        }
+
+        Ok(Self {
+            skybox,
+            irradiance,
+            prefiltered,
+            intensity,
+        })
+    }
+
+    // e.g. a decoded .hdr panorama
+    pub fn from_equirectangular(graphics_context: &GraphicsContext, image: &image::DynamicImage, intensity: f32) -> anyhow::Result<Self> {
+        if graphics_context.hdr_format() != Texture::HDR_FORMAT {
+            bail!("{:?} render targets are needed for image based lighting", Texture::HDR_FORMAT);
+        }
+        // a face covers a quarter of the panorama's width
+        let face_size = (image.width() / 4).next_power_of_two().clamp(1, MAX_SKYBOX_SIZE);
+        let renderer = CubemapRenderer::new(&graphics_context.device);
+        let skybox = Texture::cube_from_equirectangular(&graphics_context.device, &graphics_context.queue, &renderer, image, face_size, "skybox", true)?;
+        Self::from_cubemap(graphics_context, &renderer, skybox, intensity)
+    }
+
+    // the same light from every direction, doesn't need any rendering so it works everywhere
+    pub fn uniform_color(graphics_context: &GraphicsContext, color: [f32; 3], intensity: f32) -> Self {
+        let face = image::DynamicImage::ImageRgba32F(image::Rgba32FImage::from_pixel(1, 1, image::Rgba([color[0], color[1], color[2], 1.0])));
+        let faces = std::array::from_fn(|_| face.clone());
+        let cubemap = |label| Texture::cube_from_faces(&graphics_context.device, &graphics_context.queue, &faces, label, None)
+            .expect("1x1 faces are always valid");
+
+        Self {
+            skybox: cubemap("skybox"),
+            irradiance: cubemap("irradiance_map"),
+            prefiltered: cubemap("prefiltered_map"),
+            intensity,
+        }
+    }
+
+    // the mip level fully rough surfaces sample
+    pub fn max_lod(&self) -> f32 {
+        (self.prefiltered.texture.mip_level_count() - 1) as f32
+    }
+}
\ No newline at end of file
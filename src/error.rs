@@ -0,0 +1,101 @@
+use std::fmt;
+
+// Failures while starting the renderer or loading a page, shown to the visitor instead of a blank canvas
+#[derive(Debug)]
+pub enum Error {
+    Surface(wgpu::CreateSurfaceError),
+    NoAdapter,
+    Device(wgpu::RequestDeviceError),
+    Asset {
+        file_name: String,
+        source: anyhow::Error,
+    },
+}
+
+impl Error {
+    pub fn asset(file_name: &str, source: anyhow::Error) -> Self {
+        Error::Asset {
+            file_name: file_name.to_string(),
+            source,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Surface(e) if cfg!(target_arch = "wasm32") => write!(f, "WebGL2 is not supported by this browser ({})", e),
+            Error::Surface(e) => write!(f, "could not create a window surface ({})", e),
+            Error::NoAdapter if cfg!(target_arch = "wasm32") => write!(f, "WebGL2 is not supported by this browser"),
+            Error::NoAdapter => write!(f, "no compatible graphics adapter found"),
+            Error::Device(e) => write!(f, "could not open the graphics device ({})", e),
+            Error::Asset { file_name, source } => write!(f, "asset {} failed to load: {:#}", file_name, source),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Surface(e) => Some(e),
+            Error::NoAdapter => None,
+            Error::Device(e) => Some(e),
+            Error::Asset { source, .. } => Some(source.as_ref()),
+        }
+    }
+}
+
+impl From<wgpu::CreateSurfaceError> for Error {
+    fn from(e: wgpu::CreateSurfaceError) -> Self {
+        Error::Surface(e)
+    }
+}
+
+impl From<wgpu::RequestDeviceError> for Error {
+    fn from(e: wgpu::RequestDeviceError) -> Self {
+        Error::Device(e)
+    }
+}
+
+// replaces the canvas with the message, styled by the .error rules in index.html
+#[cfg(target_arch = "wasm32")]
+pub fn show(error: &Error) {
+    log::error!("{}", error);
+    let Some(document) = web_sys::window().and_then(|window| window.document()) else {
+        return;
+    };
+    let Some(body) = document.body() else {
+        return;
+    };
+    body.set_class_name("failed");
+    match document.create_element("div") {
+        Ok(message) => {
+            message.set_class_name("error");
+            message.set_text_content(Some(&format!("Sorry, this page could not be shown: {}", error)));
+            if let Err(e) = body.append_child(&message) {
+                log::error!("could not show the error message: {:?}", e);
+            }
+        }
+        Err(e) => log::error!("could not show the error message: {:?}", e),
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn show(error: &Error) {
+    log::error!("{}", error);
+}
+
+// brings the canvas back when navigating away from a page that failed to load
+#[cfg(target_arch = "wasm32")]
+pub fn hide() {
+    let Some(body) = web_sys::window().and_then(|window| window.document()).and_then(|document| document.body()) else {
+        return;
+    };
+    body.set_class_name("");
+    while let Ok(Some(message)) = body.query_selector(".error") {
+        message.remove();
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn hide() {}
\ No newline at end of file
@@ -0,0 +1,100 @@
+use cgmath::{EuclideanSpace, InnerSpace, Matrix, Matrix4, Vector4};
+use crate::bounds::{Aabb, BoundingSphere};
+use crate::camera::{CameraView, Projection};
+
+// The six planes of a camera's view volume, normals point inwards
+#[derive(Debug, Copy, Clone)]
+pub struct Frustum {
+    planes: [Vector4<f32>; 6],
+}
+
+impl Frustum {
+    // extracted from the rows of a view projection matrix with wgpu's 0..1 depth range
+    pub fn from_matrix(view_proj: &Matrix4<f32>) -> Self {
+        let (row0, row1, row2, row3) = (view_proj.row(0), view_proj.row(1), view_proj.row(2), view_proj.row(3));
+        let planes = [row3 + row0, row3 - row0, row3 + row1, row3 - row1, row2, row3 - row2]
+            .map(|plane| plane / plane.truncate().magnitude());
+        Self { planes }
+    }
+
+    pub fn new<C: CameraView>(camera: &C, projection: &Projection) -> Self {
+        Self::from_matrix(&(projection.calc_matrix() * camera.calc_matrix()))
+    }
+
+    pub fn intersects_sphere(&self, sphere: &BoundingSphere) -> bool {
+        self.planes.iter().all(|plane| plane.truncate().dot(sphere.center.to_vec()) + plane.w >= -sphere.radius)
+    }
+
+    // conservative, boxes near a corner of the frustum can pass without being visible
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        let center = aabb.center().to_vec();
+        let extents = aabb.extents();
+        self.planes.iter().all(|plane| {
+            let normal = plane.truncate();
+            let radius = extents.x * normal.x.abs() + extents.y * normal.y.abs() + extents.z * normal.z.abs();
+            normal.dot(center) + plane.w >= -radius
+        })
+    }
+}
+
+// how many mesh instances the last culling pass kept and skipped
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct CullStats {
+    pub drawn: u32,
+    pub culled: u32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{Deg, Point3, Vector3};
+
+    // looking down -z from the origin, near at 1, far at 10 and the sides at 45 degrees
+    fn frustum() -> Frustum {
+        Frustum::from_matrix(&Projection::new(1, 1, Deg(90.0), 1.0, 10.0).calc_matrix())
+    }
+
+    fn cube(center: Point3<f32>) -> Aabb {
+        let half = Vector3::new(0.1, 0.1, 0.1);
+        Aabb { min: center - half, max: center + half }
+    }
+
+    // for each plane, a point on it and the direction out of the frustum
+    fn planes() -> [(Point3<f32>, Vector3<f32>); 6] {
+        [
+            (Point3::new(0.0, 0.0, -1.0), Vector3::unit_z()),
+            (Point3::new(0.0, 0.0, -10.0), -Vector3::unit_z()),
+            (Point3::new(-5.0, 0.0, -5.0), -Vector3::unit_x()),
+            (Point3::new(5.0, 0.0, -5.0), Vector3::unit_x()),
+            (Point3::new(0.0, -5.0, -5.0), -Vector3::unit_y()),
+            (Point3::new(0.0, 5.0, -5.0), Vector3::unit_y()),
+        ]
+    }
+
+    #[test]
+    fn boxes_just_inside_each_plane_are_kept() {
+        let frustum = frustum();
+        for (point, outwards) in planes() {
+            assert!(frustum.intersects_aabb(&cube(point - outwards * 0.15)), "inside {:?}", point);
+            // straddling the plane still counts
+            assert!(frustum.intersects_aabb(&cube(point + outwards * 0.05)), "on {:?}", point);
+        }
+    }
+
+    #[test]
+    fn boxes_just_outside_each_plane_are_culled() {
+        let frustum = frustum();
+        for (point, outwards) in planes() {
+            assert!(!frustum.intersects_aabb(&cube(point + outwards * 0.25)), "outside {:?}", point);
+        }
+    }
+
+    #[test]
+    fn spheres_are_tested_against_every_plane() {
+        let frustum = frustum();
+        for (point, outwards) in planes() {
+            assert!(frustum.intersects_sphere(&BoundingSphere { center: point - outwards * 0.15, radius: 0.1 }));
+            assert!(!frustum.intersects_sphere(&BoundingSphere { center: point + outwards * 0.25, radius: 0.1 }));
+        }
+    }
+}
\ No newline at end of file
@@ -1,47 +1,122 @@
-use winit::dpi::{LogicalSize, PhysicalSize};
+use std::cell::{Cell, RefCell};
+use winit::dpi::PhysicalSize;
 use winit::window::Window;
 
+enum RenderTarget<'a> {
+    Surface(wgpu::Surface<'a>),
+    // headless contexts draw into a plain texture that can be read back
+    Offscreen(RefCell<wgpu::Texture>),
+}
+
+// the texture a page draws the current frame into
+pub struct Frame {
+    pub view: wgpu::TextureView,
+    surface_texture: Option<wgpu::SurfaceTexture>,
+}
+
+impl Frame {
+    pub fn present(self) {
+        if let Some(surface_texture) = self.surface_texture {
+            surface_texture.present();
+        }
+    }
+}
+
+// used when available, nothing depends on them yet
+const OPTIONAL_FEATURES: wgpu::Features = wgpu::Features::TEXTURE_COMPRESSION_BC
+    .union(wgpu::Features::TEXTURE_COMPRESSION_ETC2)
+    .union(wgpu::Features::TEXTURE_COMPRESSION_ASTC)
+    .union(wgpu::Features::FLOAT32_FILTERABLE);
+
+// shared by every page, resizing only needs a shared reference so pages can be loaded in the background
 pub struct GraphicsContext<'a> {
-    pub surface: wgpu::Surface<'a>,
+    target: RenderTarget<'a>,
+    pub adapter: wgpu::Adapter,
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
-    pub config: wgpu::SurfaceConfiguration,
-    pub size: PhysicalSize<u32>,
-    pub window: &'a Window,
+    config: RefCell<wgpu::SurfaceConfiguration>,
+    size: Cell<PhysicalSize<u32>>,
+    pub window: Option<&'a Window>,
+    pub assets: crate::assets::AssetManager,
 }
 
 impl<'a> GraphicsContext<'a> {
-    pub async fn new(window: &'a Window) -> Self {
-        let size = window.inner_size();
-        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::GL,
-            ..Default::default()
-        });
+    // WebGL2 in the browser, native APIs with GL as a fallback on the desktop
+    fn fallback_backends() -> wgpu::Backends {
+        if cfg!(target_arch = "wasm32") {
+            wgpu::Backends::GL
+        } else {
+            wgpu::Backends::PRIMARY | wgpu::Backends::GL
+        }
+    }
 
-        let surface = instance.create_surface(window).unwrap();
+    fn create_instance(backends: wgpu::Backends) -> wgpu::Instance {
+        wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends,
+            ..Default::default()
+        })
+    }
 
-        let adapter = instance.request_adapter(
-            &wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::HighPerformance,
-                compatible_surface: Some(&surface),
-                force_fallback_adapter: false,
-            },
-        ).await.unwrap();
+    fn adapter_options<'b>(compatible_surface: Option<&'b wgpu::Surface<'a>>, force_fallback_adapter: bool) -> wgpu::RequestAdapterOptions<'b, 'a> {
+        wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            compatible_surface,
+            force_fallback_adapter,
+        }
+    }
 
+    async fn request_device(adapter: &wgpu::Adapter, required_limits: Option<wgpu::Limits>) -> Result<(wgpu::Device, wgpu::Queue), crate::error::Error> {
+        // everything the adapter offers, GL stays on the WebGL2 defaults apart from the texture size
+        let required_limits = required_limits.unwrap_or_else(|| match adapter.get_info().backend {
+            wgpu::Backend::Gl => wgpu::Limits::downlevel_webgl2_defaults().using_resolution(adapter.limits()),
+            _ => adapter.limits(),
+        });
         let (device, queue) = adapter.request_device(
             &wgpu::DeviceDescriptor {
-                required_features: wgpu::Features::empty(),
-                required_limits: wgpu::Limits::downlevel_webgl2_defaults(),
+                required_features: adapter.features() & OPTIONAL_FEATURES,
+                required_limits,
                 label: None,
             },
             None,
-        ).await.unwrap();
+        ).await?;
+        Ok((device, queue))
+    }
 
+    pub async fn new(window: &'a Window) -> Result<Self, crate::error::Error> {
+        let size = window.inner_size();
+
+        // a canvas can't get a WebGL2 context once it has a WebGPU one,
+        // so only create the surface after WebGPU produced an adapter
+        #[cfg(target_arch = "wasm32")]
+        let webgpu = {
+            let instance = Self::create_instance(wgpu::Backends::BROWSER_WEBGPU);
+            instance.request_adapter(&Self::adapter_options(None, false)).await
+                .map(|adapter| (instance, adapter))
+        };
+        #[cfg(not(target_arch = "wasm32"))]
+        let webgpu: Option<(wgpu::Instance, wgpu::Adapter)> = None;
+
+        let (surface, adapter) = match webgpu {
+            Some((instance, adapter)) => (instance.create_surface(window)?, adapter),
+            None => {
+                let instance = Self::create_instance(Self::fallback_backends());
+                let surface = instance.create_surface(window)?;
+                let adapter = instance.request_adapter(&Self::adapter_options(Some(&surface), false)).await
+                    .ok_or(crate::error::Error::NoAdapter)?;
+                (surface, adapter)
+            }
+        };
+        log::info!("rendering with {:?} on {}", adapter.get_info().backend, adapter.get_info().name);
+
+        let (device, queue) = Self::request_device(&adapter, None).await?;
+
+        // WebGPU canvases have no sRGB formats, an sRGB view of the surface texture is used instead
         let surface_capabilities = surface.get_capabilities(&adapter);
         let surface_format = surface_capabilities.formats.iter()
             .find(|f| f.is_srgb())
             .copied()
             .unwrap_or(surface_capabilities.formats[0]);
+        let view_format = surface_format.add_srgb_suffix();
         let config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
             format: surface_format,
@@ -49,25 +124,204 @@ impl<'a> GraphicsContext<'a> {
             height: size.height.max(1),
             present_mode: surface_capabilities.present_modes[0],
             alpha_mode: surface_capabilities.alpha_modes[0],
-            view_formats: vec![],
+            view_formats: if view_format != surface_format { vec![view_format] } else { vec![] },
             desired_maximum_frame_latency: 2,
         };
         surface.configure(&device, &config);
 
-        Self {
-            surface,
+        Ok(Self {
+            target: RenderTarget::Surface(surface),
+            adapter,
             device,
             queue,
-            config,
-            size,
-            window,
+            config: RefCell::new(config),
+            size: Cell::new(size),
+            window: Some(window),
+            assets: crate::assets::AssetManager::new(),
+        })
+    }
+
+    // renders without a window, force_fallback_adapter picks a software rasterizer for reproducible output,
+    // required_limits overrides what a windowed context would ask for, e.g. to hold tests to WebGL2's limits
+    pub async fn new_headless(size: PhysicalSize<u32>, force_fallback_adapter: bool, required_limits: Option<wgpu::Limits>) -> Result<Self, crate::error::Error> {
+        let instance = Self::create_instance(Self::fallback_backends());
+
+        let adapter = instance.request_adapter(&Self::adapter_options(None, force_fallback_adapter)).await
+            .ok_or(crate::error::Error::NoAdapter)?;
+        log::info!("rendering headless on {:?}", adapter.get_info());
+
+        let (device, queue) = Self::request_device(&adapter, required_limits).await?;
+
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            width: size.width.max(1),
+            height: size.height.max(1),
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+            view_formats: vec![],
+            desired_maximum_frame_latency: 2,
+        };
+        let texture = Self::create_offscreen_texture(&device, &config);
+
+        Ok(Self {
+            target: RenderTarget::Offscreen(RefCell::new(texture)),
+            adapter,
+            device,
+            queue,
+            config: RefCell::new(config),
+            size: Cell::new(size),
+            window: None,
+            assets: crate::assets::AssetManager::new(),
+        })
+    }
+
+    fn create_offscreen_texture(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> wgpu::Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("offscreen_texture"),
+            size: wgpu::Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: config.format,
+            usage: config.usage,
+            view_formats: &[],
+        })
+    }
+
+    pub fn config(&self) -> wgpu::SurfaceConfiguration {
+        self.config.borrow().clone()
+    }
+
+    pub fn size(&self) -> PhysicalSize<u32> {
+        self.size.get()
+    }
+
+    // the format pipelines drawing to the frame have to use, always sRGB
+    pub fn format(&self) -> wgpu::TextureFormat {
+        self.config.borrow().format.add_srgb_suffix()
+    }
+
+    // Texture::HDR_FORMAT where the adapter can render to, filter and blend it,
+    // otherwise the frame's own format so lighting clips again but HDR passes still work
+    pub fn hdr_format(&self) -> wgpu::TextureFormat {
+        let features = self.adapter.get_texture_format_features(crate::texture::Texture::HDR_FORMAT);
+        let usages = wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING;
+        let flags = wgpu::TextureFormatFeatureFlags::FILTERABLE | wgpu::TextureFormatFeatureFlags::BLENDABLE;
+        if features.allowed_usages.contains(usages) && features.flags.contains(flags) {
+            crate::texture::Texture::HDR_FORMAT
+        } else {
+            self.format()
+        }
+    }
+
+    // the largest sample count up to requested that both color_format and depth_format support
+    pub fn sample_count(&self, requested: u32, color_format: wgpu::TextureFormat, depth_format: wgpu::TextureFormat) -> u32 {
+        let color = self.adapter.get_texture_format_features(color_format).flags;
+        let depth = self.adapter.get_texture_format_features(depth_format).flags;
+        [16, 8, 4, 2].into_iter()
+            .filter(|&count| count <= requested)
+            .find(|&count| color.sample_count_supported(count) && depth.sample_count_supported(count))
+            .unwrap_or(1)
+    }
+
+    pub fn backend(&self) -> wgpu::Backend {
+        self.adapter.get_info().backend
+    }
+
+    // WebGL2 has neither, pages should check before using them
+    pub fn supports_compute(&self) -> bool {
+        self.adapter.get_downlevel_capabilities().flags.contains(wgpu::DownlevelFlags::COMPUTE_SHADERS)
+            && self.device.limits().max_compute_workgroups_per_dimension > 0
+    }
+
+    pub fn supports_storage_buffers(&self) -> bool {
+        self.device.limits().max_storage_buffers_per_shader_stage > 0
+    }
+
+    pub fn current_frame(&self) -> Result<Frame, wgpu::SurfaceError> {
+        match &self.target {
+            RenderTarget::Surface(surface) => {
+                let surface_texture = surface.get_current_texture()?;
+                Ok(Frame {
+                    view: surface_texture.texture.create_view(&wgpu::TextureViewDescriptor {
+                        format: Some(self.format()),
+                        ..Default::default()
+                    }),
+                    surface_texture: Some(surface_texture),
+                })
+            }
+            RenderTarget::Offscreen(texture) => Ok(Frame {
+                view: texture.borrow().create_view(&wgpu::TextureViewDescriptor::default()),
+                surface_texture: None,
+            }),
         }
     }
 
-    pub fn resize(&mut self, new_size: PhysicalSize<u32>) {
+    // copies the last rendered frame of a headless context back to the CPU
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn read_frame(&self) -> anyhow::Result<image::RgbaImage> {
+        let RenderTarget::Offscreen(texture) = &self.target else {
+            anyhow::bail!("only headless contexts can be read back");
+        };
+        let texture = texture.borrow();
+        let (width, height) = (texture.width(), texture.height());
+
+        // rows of a buffer copy have to be aligned to 256 bytes
+        let unpadded_bytes_per_row = width * 4;
+        let padded_bytes_per_row = unpadded_bytes_per_row.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT) * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("readback_buffer"),
+            size: (padded_bytes_per_row * height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Readback encoder")
+        });
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            texture.size(),
+        );
+        self.queue.submit(std::iter::once(encoder.finish()));
+
+        let slice = buffer.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+        receiver.recv()??;
+
+        let data = slice.get_mapped_range();
+        let pixels = data.chunks(padded_bytes_per_row as usize)
+            .flat_map(|row| &row[..unpadded_bytes_per_row as usize])
+            .copied()
+            .collect();
+        drop(data);
+        buffer.unmap();
+
+        image::RgbaImage::from_raw(width, height, pixels)
+            .ok_or_else(|| anyhow::anyhow!("readback buffer does not match the frame size"))
+    }
+
+    pub fn resize(&self, new_size: PhysicalSize<u32>) {
         if new_size.width > 0 && new_size.height > 0 {
-            let window_browser = web_sys::window().unwrap();
-            let scaling = window_browser.device_pixel_ratio();
+            // device pixel ratio in the browser, headless contexts are sized in pixels already
+            let scaling = self.window.map_or(1.0, |window| window.scale_factor());
 
             let limits = self.device.limits();
 
@@ -89,10 +343,14 @@ impl<'a> GraphicsContext<'a> {
                 }
             }
 
-            self.size = new_size;
-            self.config.width = scaled_size.width;
-            self.config.height = scaled_size.height;
-            self.surface.configure(&self.device, &self.config);
+            self.size.set(new_size);
+            let mut config = self.config.borrow_mut();
+            config.width = scaled_size.width;
+            config.height = scaled_size.height;
+            match &self.target {
+                RenderTarget::Surface(surface) => surface.configure(&self.device, &config),
+                RenderTarget::Offscreen(texture) => *texture.borrow_mut() = Self::create_offscreen_texture(&self.device, &config),
+            }
         }
     }
 }
\ No newline at end of file
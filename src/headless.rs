@@ -0,0 +1,100 @@
+use std::path::{Path, PathBuf};
+use crate::grapics_context::GraphicsContext;
+use crate::runnable::Runnable;
+
+// set to regenerate the reference images instead of comparing against them
+const UPDATE_ENV: &str = "UPDATE_GOLDEN";
+
+// steps the page a few times with a fixed delta so the result doesn't depend on timing,
+// frames show the state right after the last update
+pub fn render_page(
+    graphics_context: &GraphicsContext,
+    page: &mut dyn Runnable,
+    frames: u32,
+    dt: instant::Duration,
+) -> anyhow::Result<image::RgbaImage> {
+    page.resize(graphics_context);
+    for _ in 0..frames.max(1) {
+        page.update(graphics_context, dt);
+        page.render(graphics_context, 1.0)?;
+    }
+    graphics_context.read_frame()
+}
+
+pub fn save_png(image: &image::RgbaImage, path: impl AsRef<Path>) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    image.save_with_format(path, image::ImageFormat::Png)
+        .map_err(|e| anyhow::anyhow!("could not write {}: {}", path.display(), e))
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ImageDiff {
+    pub mismatched_pixels: usize,
+    pub max_channel_difference: u8,
+}
+
+// pixels count as mismatched once any channel differs by more than channel_tolerance
+pub fn compare_images(actual: &image::RgbaImage, expected: &image::RgbaImage, channel_tolerance: u8) -> anyhow::Result<ImageDiff> {
+    if actual.dimensions() != expected.dimensions() {
+        anyhow::bail!("image size {:?} does not match reference size {:?}", actual.dimensions(), expected.dimensions());
+    }
+
+    let mut diff = ImageDiff {
+        mismatched_pixels: 0,
+        max_channel_difference: 0,
+    };
+    for (a, e) in actual.pixels().zip(expected.pixels()) {
+        let difference = a.0.iter().zip(e.0.iter())
+            .map(|(a, e)| a.abs_diff(*e))
+            .max()
+            .unwrap_or(0);
+        diff.max_channel_difference = diff.max_channel_difference.max(difference);
+        if difference > channel_tolerance {
+            diff.mismatched_pixels += 1;
+        }
+    }
+    Ok(diff)
+}
+
+// Compares a frame against a stored reference, different drivers rasterize edges slightly differently
+// so a small fraction of pixels is allowed to be off. Failing frames are written next to the reference.
+pub fn assert_matches_reference(
+    actual: &image::RgbaImage,
+    reference: impl AsRef<Path>,
+    channel_tolerance: u8,
+    max_mismatched_fraction: f32,
+) -> anyhow::Result<()> {
+    let reference = reference.as_ref();
+    if std::env::var_os(UPDATE_ENV).is_some() {
+        log::info!("updating reference image {}", reference.display());
+        return save_png(actual, reference);
+    }
+    if !reference.exists() {
+        anyhow::bail!("reference image {} is missing, run with {}=1 to create it", reference.display(), UPDATE_ENV);
+    }
+
+    let expected = image::open(reference)
+        .map_err(|e| anyhow::anyhow!("could not read {}: {}", reference.display(), e))?
+        .to_rgba8();
+    let diff = compare_images(actual, &expected, channel_tolerance);
+    let mismatched_fraction = diff.as_ref()
+        .map(|diff| diff.mismatched_pixels as f32 / (actual.width() * actual.height()) as f32)
+        .unwrap_or(1.0);
+    if mismatched_fraction > max_mismatched_fraction {
+        let actual_path = actual_path(reference);
+        save_png(actual, &actual_path)?;
+        anyhow::bail!(
+            "{} does not match, {:?} ({:.3}% of pixels), frame written to {}",
+            reference.display(), diff, mismatched_fraction * 100.0, actual_path.display()
+        );
+    }
+    Ok(())
+}
+
+fn actual_path(reference: &Path) -> PathBuf {
+    let stem = reference.file_stem().unwrap_or_default().to_string_lossy();
+    reference.with_file_name(format!("{}.actual.png", stem))
+}
\ No newline at end of file
@@ -1,18 +1,42 @@
-mod runnable;
-mod wip_page;
-mod grapics_context;
-mod wgpu_helpers;
-mod texture;
-mod resources;
-mod model;
-mod camera;
+pub mod runnable;
+pub mod router;
+pub mod wip_page;
+pub mod grapics_context;
+pub mod wgpu_helpers;
+pub mod texture;
+pub mod resources;
+pub mod model;
+pub mod camera;
+pub mod light;
+pub mod shadow;
+pub mod scene;
+pub mod touch;
+pub mod assets;
+pub mod progress;
+pub mod loading_screen;
+pub mod post_process;
+pub mod environment;
+pub mod skybox;
+pub mod timing;
+pub mod bounds;
+pub mod frustum;
+pub mod picking;
+pub mod text;
+pub mod animation;
+pub mod morph;
+pub mod error;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod headless;
 
+#[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::wasm_bindgen;
 use winit::dpi::PhysicalSize;
 use winit::event_loop::EventLoop;
+#[cfg(target_arch = "wasm32")]
 use winit::platform::web::WindowBuilderExtWebSys;
 use winit::window::{Window, WindowBuilder};
 
+#[cfg(target_arch = "wasm32")]
 fn create_window(event_loop: &EventLoop<()>) -> Window {
     let window_browser = web_sys::window().unwrap();
     let scaling = window_browser.device_pixel_ratio();
@@ -30,6 +54,16 @@ fn create_window(event_loop: &EventLoop<()>) -> Window {
     window
 }
 
+#[cfg(not(target_arch = "wasm32"))]
+fn create_window(event_loop: &EventLoop<()>) -> Window {
+    WindowBuilder::new()
+        .with_title("Mateusz Hurnik Personal Site")
+        .with_inner_size(PhysicalSize::new(1280, 720))
+        .build(event_loop)
+        .unwrap()
+}
+
+#[cfg(target_arch = "wasm32")]
 #[wasm_bindgen(start)]
 fn main() {
     // logging setup
@@ -39,7 +73,22 @@ fn main() {
     wasm_bindgen_futures::spawn_local(async {
         // window setup
         let event_loop = EventLoop::new().unwrap();
-        let window = create_window(&event_loop);
-        runnable::run::<wip_page::WipPage>(event_loop, &window).await
+        // the window lives as long as the page, pages share it through the graphics context
+        let window: &'static Window = Box::leak(Box::new(create_window(&event_loop)));
+        if let Err(e) = runnable::run(event_loop, window, timing::TimingConfig::default()).await {
+            error::show(&e);
+        }
     });
+}
+
+// Entry point of the desktop build, assets are read from ./assets instead of being fetched
+#[cfg(not(target_arch = "wasm32"))]
+pub fn run_native() {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+
+    let event_loop = EventLoop::new().unwrap();
+    let window: &'static Window = Box::leak(Box::new(create_window(&event_loop)));
+    if let Err(e) = pollster::block_on(runnable::run(event_loop, window, timing::TimingConfig::default())) {
+        error::show(&e);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,200 @@
+use bytemuck::{Pod, Zeroable};
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, Point3, Rad, SquareMatrix, Vector3};
+use crate::shadow::{ShadowConfig, MAX_SHADOW_MAPS};
+
+// the fragment shader loops over a fixed size uniform array, WebGL2 has no storage buffers
+pub const MAX_LIGHTS: usize = 16;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum LightKind {
+    Point,
+    Directional,
+    Spot {
+        inner_angle: Rad<f32>,
+        outer_angle: Rad<f32>,
+    },
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct Light {
+    pub kind: LightKind,
+    pub position: Point3<f32>,
+    pub direction: Vector3<f32>,
+    pub color: Vector3<f32>,
+    pub intensity: f32,
+    // distance at which the light fades out completely, 0.0 means unlimited
+    pub range: f32,
+    // only directional and spot lights can cast shadows
+    pub cast_shadows: bool,
+}
+
+impl Light {
+    pub fn point<P: Into<Point3<f32>>, C: Into<Vector3<f32>>>(position: P, color: C, intensity: f32, range: f32) -> Self {
+        Self {
+            kind: LightKind::Point,
+            position: position.into(),
+            direction: -Vector3::unit_y(),
+            color: color.into(),
+            intensity,
+            range,
+            cast_shadows: false,
+        }
+    }
+
+    pub fn directional<D: Into<Vector3<f32>>, C: Into<Vector3<f32>>>(direction: D, color: C, intensity: f32) -> Self {
+        Self {
+            kind: LightKind::Directional,
+            position: Point3::new(0.0, 0.0, 0.0),
+            direction: direction.into().normalize(),
+            color: color.into(),
+            intensity,
+            range: 0.0,
+            cast_shadows: false,
+        }
+    }
+
+    pub fn spot<
+        P: Into<Point3<f32>>,
+        D: Into<Vector3<f32>>,
+        I: Into<Rad<f32>>,
+        O: Into<Rad<f32>>,
+        C: Into<Vector3<f32>>
+    >(
+        position: P,
+        direction: D,
+        inner_angle: I,
+        outer_angle: O,
+        color: C,
+        intensity: f32,
+        range: f32,
+    ) -> Self {
+        Self {
+            kind: LightKind::Spot {
+                inner_angle: inner_angle.into(),
+                outer_angle: outer_angle.into(),
+            },
+            position: position.into(),
+            direction: direction.into().normalize(),
+            color: color.into(),
+            intensity,
+            range,
+            cast_shadows: false,
+        }
+    }
+
+    pub fn with_shadows(mut self) -> Self {
+        self.cast_shadows = true;
+        self
+    }
+
+    // projection used to render the shadow map, None if the light doesn't cast shadows
+    pub fn shadow_view_proj(&self, config: &ShadowConfig) -> Option<Matrix4<f32>> {
+        if !self.cast_shadows {
+            return None;
+        }
+        let up = if self.direction.y.abs() > 0.99 { Vector3::unit_z() } else { Vector3::unit_y() };
+        match self.kind {
+            LightKind::Point => None,
+            LightKind::Directional => {
+                let extent = config.directional_extent;
+                let eye = config.directional_center - self.direction * extent;
+                let view = Matrix4::look_at_rh(eye, config.directional_center, up);
+                let projection = cgmath::ortho(-extent, extent, -extent, extent, 0.0, 2.0 * extent);
+                Some(crate::camera::OPENGL_TO_WGPU_MATRIX * projection * view)
+            }
+            LightKind::Spot { outer_angle, .. } => {
+                let view = Matrix4::look_to_rh(self.position, self.direction, up);
+                let far = if self.range > 0.0 { self.range } else { config.directional_extent * 2.0 };
+                let projection = cgmath::perspective(outer_angle * 2.0, 1.0, 0.05, far);
+                Some(crate::camera::OPENGL_TO_WGPU_MATRIX * projection * view)
+            }
+        }
+    }
+
+    pub fn to_raw(&self, shadow_layer: Option<u32>, config: &ShadowConfig) -> LightRaw {
+        let (kind, cone) = match self.kind {
+            LightKind::Point => (0.0, [0.0, 0.0]),
+            LightKind::Directional => (1.0, [0.0, 0.0]),
+            LightKind::Spot { inner_angle, outer_angle } => (2.0, [inner_angle.0.cos(), outer_angle.0.cos()]),
+        };
+        let view_proj = shadow_layer
+            .and(self.shadow_view_proj(config))
+            .unwrap_or(Matrix4::identity());
+        LightRaw {
+            position: self.position.to_vec().extend(kind).into(),
+            direction: self.direction.extend(self.range).into(),
+            color: self.color.extend(self.intensity).into(),
+            cone: [cone[0], cone[1], shadow_layer.map_or(-1.0, |layer| layer as f32), 0.0],
+            view_proj: view_proj.into(),
+        }
+    }
+}
+
+// assigns a shadow map layer to every shadow casting light, in order
+pub fn shadow_layers(lights: &[Light], config: &ShadowConfig) -> Vec<Option<u32>> {
+    let mut next_layer = 0;
+    lights.iter()
+        .map(|light| {
+            if next_layer < MAX_SHADOW_MAPS && light.shadow_view_proj(config).is_some() {
+                next_layer += 1;
+                Some(next_layer - 1)
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+pub struct LightRaw {
+    // w is the light kind
+    position: [f32; 4],
+    // w is the range
+    direction: [f32; 4],
+    // w is the intensity
+    color: [f32; 4],
+    // cosines of the inner and outer spot angles, shadow map layer or -1
+    cone: [f32; 4],
+    view_proj: [[f32; 4]; 4],
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+pub struct LightsUniform {
+    lights: [LightRaw; MAX_LIGHTS],
+    count: u32,
+    shadow_bias: f32,
+    shadow_normal_bias: f32,
+    shadow_texel_size: f32,
+    environment_intensity: f32,
+    environment_max_lod: f32,
+    _padding: [f32; 2],
+}
+
+impl LightsUniform {
+    pub fn new(lights: &[Light], config: &ShadowConfig) -> Self {
+        let mut uniform = Self::zeroed();
+        uniform.update(lights, config);
+        uniform
+    }
+
+    pub fn update(&mut self, lights: &[Light], config: &ShadowConfig) {
+        if lights.len() > MAX_LIGHTS {
+            log::warn!("{} lights in the scene, only the first {} are used", lights.len(), MAX_LIGHTS);
+        }
+        let count = lights.len().min(MAX_LIGHTS);
+        for ((raw, light), layer) in self.lights.iter_mut().zip(lights).zip(shadow_layers(lights, config)) {
+            *raw = light.to_raw(layer, config);
+        }
+        self.count = count as u32;
+        self.shadow_bias = config.bias;
+        self.shadow_normal_bias = config.normal_bias;
+        self.shadow_texel_size = 1.0 / config.resolution as f32;
+    }
+
+    pub fn set_environment(&mut self, environment: &crate::environment::Environment) {
+        self.environment_intensity = environment.intensity;
+        self.environment_max_lod = environment.max_lod();
+    }
+}
\ No newline at end of file
@@ -0,0 +1,115 @@
+use bytemuck::{Pod, Zeroable};
+use wgpu::util::DeviceExt;
+use crate::grapics_context::GraphicsContext;
+use crate::progress::Progress;
+use crate::wgpu_helpers::{BlendMode, RenderPipelineBuilder};
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+struct LoadingUniform {
+    resolution: [f32; 2],
+    progress: f32,
+    time: f32,
+}
+
+// Shown by the runner until the requested page has finished loading its assets
+pub struct LoadingScreen {
+    pipeline: wgpu::RenderPipeline,
+    uniform: LoadingUniform,
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+impl LoadingScreen {
+    pub fn new(graphics_context: &GraphicsContext) -> Self {
+        let device = &graphics_context.device;
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Loading Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/loading.wgsl").into()),
+        });
+
+        let pipeline = RenderPipelineBuilder::new(&shader)
+            .label("Loading Pipeline")
+            .color_target(graphics_context.format(), BlendMode::Replace)
+            .cull_mode(None)
+            .build(device);
+
+        let uniform = LoadingUniform {
+            resolution: [1.0, 1.0],
+            progress: 0.0,
+            time: 0.0,
+        };
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Loading Buffer"),
+            contents: bytemuck::cast_slice(&[uniform]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                }
+            ],
+            label: Some("loading bind group"),
+        });
+
+        Self {
+            pipeline,
+            uniform,
+            uniform_buffer,
+            bind_group,
+        }
+    }
+
+    // restarts the bar when a new page starts loading
+    pub fn reset(&mut self) {
+        self.uniform.progress = 0.0;
+        self.uniform.time = 0.0;
+    }
+
+    pub fn update(&mut self, graphics_context: &GraphicsContext, dt: instant::Duration, progress: Progress) {
+        let dt = dt.as_secs_f32();
+        // ease towards the real value so the bar doesn't jump as new downloads get discovered
+        let target = progress.fraction().max(self.uniform.progress);
+        self.uniform.progress += (target - self.uniform.progress) * (1.0 - (-8.0 * dt).exp());
+        self.uniform.time += dt;
+
+        let config = graphics_context.config();
+        self.uniform.resolution = [config.width as f32, config.height as f32];
+        graphics_context.queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[self.uniform]));
+    }
+
+    pub fn render(&self, graphics_context: &GraphicsContext) -> Result<(), wgpu::SurfaceError> {
+        let frame = graphics_context.current_frame()?;
+
+        let mut encoder = graphics_context.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Loading encoder")
+        });
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Loading pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &frame.view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            render_pass.set_pipeline(&self.pipeline);
+            render_pass.set_bind_group(0, &self.bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+
+        graphics_context.queue.submit(std::iter::once(encoder.finish()));
+        frame.present();
+
+        Ok(())
+    }
+}
\ No newline at end of file
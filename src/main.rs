@@ -0,0 +1,5 @@
+fn main() {
+    // the web build starts from the wasm_bindgen entry point in lib.rs instead
+    #[cfg(not(target_arch = "wasm32"))]
+    personal_page::run_native();
+}
\ No newline at end of file
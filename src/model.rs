@@ -1,7 +1,11 @@
 use std::ops::Range;
 use bytemuck::{Pod, Zeroable};
-use cgmath::{Matrix4, Quaternion, Vector3};
+use cgmath::{InnerSpace, Matrix, Matrix3, Matrix4, One, Point3, Quaternion, SquareMatrix, Vector3, Zero};
+use wgpu::util::DeviceExt;
 use wgpu::{BindGroup, VertexBufferLayout};
+use crate::animation::{AnimationClip, Skeleton};
+use crate::bounds::{Aabb, BoundingSphere};
+use crate::morph::MorphTargets;
 
 pub trait Vertex {
     fn desc() -> wgpu::VertexBufferLayout<'static>;
@@ -15,6 +19,9 @@ pub struct ModelVertex {
     pub normal: [f32; 3],
     pub tangent: [f32; 3],
     pub bitangent: [f32; 3],
+    // up to four joints of the model's skeleton, all weights are 0 for meshes without one
+    pub joints: [u16; 4],
+    pub weights: [f32; 4],
 }
 
 impl Vertex for ModelVertex {
@@ -49,29 +56,232 @@ impl Vertex for ModelVertex {
                     shader_location: 4,
                     format: wgpu::VertexFormat::Float32x3,
                 },
+                // after the instance attributes
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 14]>() as wgpu::BufferAddress,
+                    shader_location: 12,
+                    format: wgpu::VertexFormat::Uint16x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: (mem::size_of::<[f32; 14]>() + mem::size_of::<[u16; 4]>()) as wgpu::BufferAddress,
+                    shader_location: 13,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
             ],
         }
     }
 }
 
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+pub struct MaterialUniform {
+    pub base_color_factor: [f32; 4],
+    pub emissive_factor: [f32; 3],
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+    pub occlusion_strength: f32,
+    pub normal_scale: f32,
+    pub _padding: f32,
+}
+
+pub struct MaterialTextures {
+    pub base_color: crate::assets::Handle<crate::texture::Texture>,
+    pub normal: crate::assets::Handle<crate::texture::Texture>,
+    pub metallic_roughness: crate::assets::Handle<crate::texture::Texture>,
+    pub occlusion: crate::assets::Handle<crate::texture::Texture>,
+    pub emissive: crate::assets::Handle<crate::texture::Texture>,
+}
+
 pub struct Material {
     pub name: String,
-    pub diffuse_texture: crate::texture::Texture,
-    pub normal_texture: crate::texture::Texture,
+    pub textures: MaterialTextures,
+    pub uniform: MaterialUniform,
+    pub uniform_buffer: wgpu::Buffer,
     pub bind_group: wgpu::BindGroup,
 }
 
+impl Material {
+    pub fn create_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            },
+            count: None,
+        };
+        let sampler_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+            count: None,
+        };
+
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                // base color
+                texture_entry(0),
+                sampler_entry(1),
+                // normal
+                texture_entry(2),
+                sampler_entry(3),
+                // metallic (blue) and roughness (green)
+                texture_entry(4),
+                sampler_entry(5),
+                // occlusion (red)
+                texture_entry(6),
+                sampler_entry(7),
+                // emissive
+                texture_entry(8),
+                sampler_entry(9),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 10,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("texture_bind_group_layout"),
+        })
+    }
+
+    pub fn new(
+        device: &wgpu::Device,
+        name: &str,
+        textures: MaterialTextures,
+        uniform: MaterialUniform,
+        layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{:?} Material Buffer", name)),
+            contents: bytemuck::cast_slice(&[uniform]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&textures.base_color.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&textures.base_color.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&textures.normal.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::Sampler(&textures.normal.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::TextureView(&textures.metallic_roughness.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: wgpu::BindingResource::Sampler(&textures.metallic_roughness.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 6,
+                    resource: wgpu::BindingResource::TextureView(&textures.occlusion.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 7,
+                    resource: wgpu::BindingResource::Sampler(&textures.occlusion.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 8,
+                    resource: wgpu::BindingResource::TextureView(&textures.emissive.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 9,
+                    resource: wgpu::BindingResource::Sampler(&textures.emissive.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 10,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+            ],
+            label: Some(name),
+        });
+
+        Self {
+            name: name.to_string(),
+            textures,
+            uniform,
+            uniform_buffer,
+            bind_group,
+        }
+    }
+}
+
 pub struct Mesh {
     pub name: String,
     pub vertex_buffer: wgpu::Buffer,
     pub index_buffer: wgpu::Buffer,
     pub num_elements: u32,
     pub material: usize,
+    // in the mesh's own space and bind pose, used for culling
+    pub aabb: Aabb,
+    pub bounding_sphere: BoundingSphere,
+    // a CPU copy of the triangles for picking
+    pub positions: Vec<Point3<f32>>,
+    pub indices: Vec<u32>,
+    // set through set_morph_targets so the bounds cover the morphed shape
+    pub morph_targets: Option<MorphTargets>,
+}
+
+impl Mesh {
+    pub fn new(device: &wgpu::Device, name: &str, vertices: &[ModelVertex], indices: &[u32], material: usize) -> Self {
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{:?} Vertex Buffer", name)),
+            contents: bytemuck::cast_slice(vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{:?} Index Buffer", name)),
+            contents: bytemuck::cast_slice(indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+        let positions: Vec<Point3<f32>> = vertices.iter().map(|vertex| Point3::from(vertex.position)).collect();
+
+        Self {
+            name: name.to_string(),
+            vertex_buffer,
+            index_buffer,
+            num_elements: indices.len() as u32,
+            material,
+            aabb: Aabb::from_points(positions.iter().copied()),
+            bounding_sphere: BoundingSphere::from_points(&positions),
+            positions,
+            indices: indices.to_vec(),
+            morph_targets: None,
+        }
+    }
+
+    // the bounds grow by the furthest a vertex can be morphed so culling never drops a morphed mesh
+    pub fn set_morph_targets(&mut self, morph_targets: MorphTargets) {
+        self.aabb = self.aabb.grow(morph_targets.max_offset);
+        self.bounding_sphere.radius += morph_targets.max_offset;
+        self.morph_targets = Some(morph_targets);
+    }
 }
 
 pub struct Model {
     pub meshes: Vec<Mesh>,
     pub materials: Vec<Material>,
+    pub skeleton: Option<Skeleton>,
+    pub animations: Vec<AnimationClip>,
 }
 
 pub trait DrawModel<'a> {
@@ -92,8 +302,8 @@ where
 
     fn draw_mesh_instanced(&mut self, mesh: &'b Mesh, material: &'b Material, camera_bind_group: &'b wgpu::BindGroup, light_bind_group: &'a wgpu::BindGroup, instances: Range<u32>) {
         self.set_bind_group(0, &material.bind_group, &[]);
-        self.set_bind_group(1, &camera_bind_group, &[]);
-        self.set_bind_group(2, &light_bind_group, &[]);
+        self.set_bind_group(1, camera_bind_group, &[]);
+        self.set_bind_group(2, light_bind_group, &[]);
         self.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
         self.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
         self.draw_indexed(0..mesh.num_elements, 0, instances);
@@ -112,9 +322,11 @@ where
 }
 
 
+#[derive(Copy, Clone)]
 pub struct Instance {
     pub position: Vector3<f32>,
     pub rotation: Quaternion<f32>,
+    pub scale: Vector3<f32>,
 }
 
 #[repr(C)]
@@ -125,15 +337,67 @@ pub struct InstanceRaw {
 }
 
 impl Instance {
+    // splits an affine transform back into translation, rotation and scale
+    pub fn from_matrix(matrix: Matrix4<f32>) -> Self {
+        let mut basis = Matrix3::from_cols(matrix.x.truncate(), matrix.y.truncate(), matrix.z.truncate());
+        let scale = Vector3::new(
+            basis.x.magnitude(),
+            basis.y.magnitude(),
+            basis.determinant().signum() * basis.z.magnitude(),
+        );
+        // a zero scale (glTF's way of hiding a node) leaves no basis to take the rotation from
+        let rotation = if scale.x == 0.0 || scale.y == 0.0 || scale.z == 0.0 {
+            Quaternion::one()
+        } else {
+            basis.x /= scale.x;
+            basis.y /= scale.y;
+            basis.z /= scale.z;
+            Quaternion::from(basis)
+        };
+
+        Self {
+            position: matrix.w.truncate(),
+            rotation,
+            scale,
+        }
+    }
+
+    pub fn identity() -> Self {
+        Self {
+            position: Vector3::zero(),
+            rotation: Quaternion::one(),
+            scale: Vector3::new(1.0, 1.0, 1.0),
+        }
+    }
+
+    pub fn to_matrix(&self) -> Matrix4<f32> {
+        Matrix4::from_translation(self.position)
+            * Matrix4::from(self.rotation)
+            * Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z)
+    }
+
     pub fn to_raw(&self) -> InstanceRaw {
+        // inverse transpose of rotation * scale
+        let inverse_scale = Matrix3::from_diagonal(Vector3::new(1.0 / self.scale.x, 1.0 / self.scale.y, 1.0 / self.scale.z));
         InstanceRaw {
-            model: (Matrix4::from_translation(self.position) * Matrix4::from(self.rotation)).into(),
-            normal: cgmath::Matrix3::from(self.rotation).into(),
+            model: self.to_matrix().into(),
+            normal: (Matrix3::from(self.rotation) * inverse_scale).into(),
         }
     }
 }
 
 impl InstanceRaw {
+    pub fn from_matrix(model: Matrix4<f32>) -> Self {
+        let basis = Matrix3::from_cols(model.x.truncate(), model.y.truncate(), model.z.truncate());
+        let normal = basis.invert()
+            .map(|inverse| inverse.transpose())
+            .unwrap_or(Matrix3::identity());
+        Self {
+            model: model.into(),
+            normal: normal.into(),
+        }
+    }
+
     pub fn desc() -> wgpu::VertexBufferLayout<'static> {
         use std::mem;
         wgpu::VertexBufferLayout {
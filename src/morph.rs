@@ -0,0 +1,192 @@
+use bytemuck::{Pod, Zeroable};
+use wgpu::util::DeviceExt;
+
+// has to match MAX_MORPH_TARGETS in wip.wgsl and shadow.wgsl
+pub const MAX_MORPH_TARGETS: usize = 8;
+// the smallest maximum texture size WebGL2 guarantees
+const TEXTURE_WIDTH: usize = 2048;
+
+// Offsets from the base mesh, one per vertex
+pub struct MorphTarget {
+    pub positions: Vec<[f32; 3]>,
+    // empty when the target doesn't change normals
+    pub normals: Vec<[f32; 3]>,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+struct MorphUniform {
+    weights: [f32; MAX_MORPH_TARGETS],
+    count: u32,
+    vertex_count: u32,
+    texture_width: u32,
+    _padding: u32,
+}
+
+// A mesh's morph targets, the deltas live in a texture read by vertex index
+// so they don't take up vertex attributes, which WebGL2 only has 16 of
+pub struct MorphTargets {
+    pub count: usize,
+    // what the weights are when nothing animates them
+    pub default_weights: Vec<f32>,
+    // how far any vertex can move from the base mesh with every weight at 1
+    pub max_offset: f32,
+    uniform_buffer: wgpu::Buffer,
+    pub bind_group: wgpu::BindGroup,
+}
+
+impl MorphTargets {
+    pub fn create_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("morph bind group layout"),
+        })
+    }
+
+    // targets past MAX_MORPH_TARGETS are dropped
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layout: &wgpu::BindGroupLayout,
+        vertex_count: usize,
+        targets: &[MorphTarget],
+        default_weights: &[f32],
+        label: &str,
+    ) -> anyhow::Result<Self> {
+        if targets.len() > MAX_MORPH_TARGETS {
+            log::warn!("{} has {} morph targets, only the first {} are used", label, targets.len(), MAX_MORPH_TARGETS);
+        }
+        let targets = &targets[..targets.len().min(MAX_MORPH_TARGETS)];
+
+        // a position and a normal texel per vertex, target after target
+        let texels = (2 * vertex_count * targets.len()).max(1);
+        let height = texels.div_ceil(TEXTURE_WIDTH);
+        if height > TEXTURE_WIDTH {
+            anyhow::bail!("{} has too many morphed vertices to fit in a texture", label);
+        }
+        let mut data = vec![[0.0f32; 4]; TEXTURE_WIDTH * height];
+        for (target_index, target) in targets.iter().enumerate() {
+            let start = 2 * vertex_count * target_index;
+            for (vertex, position) in target.positions.iter().enumerate().take(vertex_count) {
+                data[start + 2 * vertex] = [position[0], position[1], position[2], 0.0];
+            }
+            for (vertex, normal) in target.normals.iter().enumerate().take(vertex_count) {
+                data[start + 2 * vertex + 1] = [normal[0], normal[1], normal[2], 0.0];
+            }
+        }
+        let texture = device.create_texture_with_data(queue, &wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: TEXTURE_WIDTH as u32,
+                height: height as u32,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba32Float,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        }, wgpu::util::TextureDataOrder::LayerMajor, bytemuck::cast_slice(&data));
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let mut uniform = MorphUniform {
+            weights: [0.0; MAX_MORPH_TARGETS],
+            count: targets.len() as u32,
+            vertex_count: vertex_count as u32,
+            texture_width: TEXTURE_WIDTH as u32,
+            _padding: 0,
+        };
+        for (weight, default) in uniform.weights.iter_mut().zip(default_weights) {
+            *weight = *default;
+        }
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(label),
+            contents: bytemuck::cast_slice(&[uniform]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+            ],
+            label: Some(label),
+        });
+
+        Ok(Self {
+            count: targets.len(),
+            default_weights: uniform.weights[..targets.len()].to_vec(),
+            max_offset: max_offset(targets, vertex_count),
+            uniform_buffer,
+            bind_group,
+        })
+    }
+
+    // no targets, bound for meshes that don't have any
+    pub fn empty(device: &wgpu::Device, queue: &wgpu::Queue, layout: &wgpu::BindGroupLayout) -> Self {
+        Self::new(device, queue, layout, 0, &[], &[], "empty morph targets")
+            .expect("an empty morph texture always fits")
+    }
+
+    // takes &self so meshes of models shared through Rc can be animated, missing weights are set to 0
+    pub fn set_weights(&self, queue: &wgpu::Queue, weights: &[f32]) {
+        let mut padded = [0.0f32; MAX_MORPH_TARGETS];
+        for (weight, new) in padded.iter_mut().zip(weights).take(self.count) {
+            *weight = *new;
+        }
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&padded));
+    }
+}
+
+// the largest per-vertex sum of the targets' offsets, active targets add up
+fn max_offset(targets: &[MorphTarget], vertex_count: usize) -> f32 {
+    (0..vertex_count)
+        .map(|vertex| targets.iter()
+            .filter_map(|target| target.positions.get(vertex))
+            .map(|[x, y, z]| (x * x + y * y + z * z).sqrt())
+            .sum::<f32>())
+        .fold(0.0, f32::max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn max_offset_adds_up_the_targets_of_each_vertex() {
+        let targets = [
+            MorphTarget { positions: vec![[3.0, 4.0, 0.0], [0.0, 0.0, 0.0]], normals: Vec::new() },
+            MorphTarget { positions: vec![[0.0, 0.0, -1.0], [0.0, 5.5, 0.0]], normals: Vec::new() },
+        ];
+        assert_eq!(max_offset(&targets, 2), 6.0);
+        assert_eq!(max_offset(&[], 2), 0.0);
+    }
+}
\ No newline at end of file
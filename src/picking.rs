@@ -0,0 +1,171 @@
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, Point3, SquareMatrix, Transform, Vector3, Vector4};
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+use crate::bounds::Aabb;
+use crate::camera::{CameraView, Projection};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Ray {
+    pub origin: Point3<f32>,
+    pub direction: Vector3<f32>,
+}
+
+impl Ray {
+    pub fn new(origin: Point3<f32>, direction: Vector3<f32>) -> Self {
+        Self { origin, direction }
+    }
+
+    // from the camera through a cursor position in window pixels
+    pub fn from_cursor<C: CameraView>(position: PhysicalPosition<f64>, size: PhysicalSize<u32>, camera: &C, projection: &Projection) -> Option<Self> {
+        let inverse = (projection.calc_matrix() * camera.calc_matrix()).invert()?;
+        let x = (2.0 * position.x / size.width.max(1) as f64 - 1.0) as f32;
+        let y = (1.0 - 2.0 * position.y / size.height.max(1) as f64) as f32;
+        let unproject = |depth: f32| {
+            let point = inverse * Vector4::new(x, y, depth, 1.0);
+            Point3::from_vec(point.truncate() / point.w)
+        };
+        // wgpu's depth goes from 0 at the near plane to 1 at the far one
+        let near = unproject(0.0);
+        let far = unproject(1.0);
+        Some(Self::new(near, (far - near).normalize()))
+    }
+
+    pub fn at(&self, distance: f32) -> Point3<f32> {
+        self.origin + self.direction * distance
+    }
+
+    // the direction is left unnormalized so distances along both rays match
+    pub fn transform(&self, matrix: &Matrix4<f32>) -> Self {
+        Self::new(matrix.transform_point(self.origin), matrix.transform_vector(self.direction))
+    }
+
+    // slab test, the distance to where the ray enters the box or 0 when it starts inside
+    pub fn intersect_aabb(&self, aabb: &Aabb) -> Option<f32> {
+        let mut near = 0.0f32;
+        let mut far = f32::INFINITY;
+        for axis in 0..3 {
+            let inverse = 1.0 / self.direction[axis];
+            let t0 = (aabb.min[axis] - self.origin[axis]) * inverse;
+            let t1 = (aabb.max[axis] - self.origin[axis]) * inverse;
+            near = near.max(t0.min(t1));
+            far = far.min(t0.max(t1));
+        }
+        (near <= far).then_some(near)
+    }
+
+    // Möller-Trumbore, both sides of the triangle count as a hit
+    pub fn intersect_triangle(&self, a: Point3<f32>, b: Point3<f32>, c: Point3<f32>) -> Option<f32> {
+        let edge1 = b - a;
+        let edge2 = c - a;
+        let p = self.direction.cross(edge2);
+        let determinant = edge1.dot(p);
+        if determinant.abs() < f32::EPSILON {
+            return None;
+        }
+        let inverse = 1.0 / determinant;
+        let s = self.origin - a;
+        let u = s.dot(p) * inverse;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let q = s.cross(edge1);
+        let v = self.direction.dot(q) * inverse;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+        let distance = edge2.dot(q) * inverse;
+        (distance > 0.0).then_some(distance)
+    }
+}
+
+// The closest surface under a ray, indices are into Scene::models, Model::meshes and the model's instances
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PickHit {
+    pub model: usize,
+    pub mesh: usize,
+    pub instance: usize,
+    pub distance: f32,
+    pub point: Point3<f32>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{assert_abs_diff_eq, Deg, MetricSpace};
+    use crate::camera::OrbitCamera;
+
+    fn unit_box() -> Aabb {
+        Aabb { min: Point3::new(-1.0, -1.0, -1.0), max: Point3::new(1.0, 1.0, 1.0) }
+    }
+
+    fn triangle() -> [Point3<f32>; 3] {
+        [Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 0.0, 0.0), Point3::new(0.0, 1.0, 0.0)]
+    }
+
+    #[test]
+    fn ray_enters_the_box_at_the_near_slab() {
+        let ray = Ray::new(Point3::new(-5.0, 0.5, 0.2), Vector3::new(1.0, 0.1, 0.0).normalize());
+        let distance = ray.intersect_aabb(&unit_box()).unwrap();
+        assert_abs_diff_eq!(ray.at(distance).x, -1.0, epsilon = 1e-5);
+    }
+
+    #[test]
+    fn ray_passing_beside_the_box_misses() {
+        let ray = Ray::new(Point3::new(-5.0, 0.0, 0.0), Vector3::new(1.0, 1.0, 0.0).normalize());
+        assert_eq!(ray.intersect_aabb(&unit_box()), None);
+    }
+
+    #[test]
+    fn ray_starting_inside_the_box_hits_at_zero() {
+        let ray = Ray::new(Point3::new(0.0, 0.0, 0.0), Vector3::unit_x());
+        assert_eq!(ray.intersect_aabb(&unit_box()), Some(0.0));
+    }
+
+    // a zero component divides to infinity, the slab on that axis then either spans everything or nothing
+    #[test]
+    fn axis_parallel_rays_hit_only_inside_the_other_slabs() {
+        let inside = Ray::new(Point3::new(0.5, 0.5, 5.0), -Vector3::unit_z());
+        assert_eq!(inside.intersect_aabb(&unit_box()), Some(4.0));
+        let beside = Ray::new(Point3::new(1.5, 0.5, 5.0), -Vector3::unit_z());
+        assert_eq!(beside.intersect_aabb(&unit_box()), None);
+        let below = Ray::new(Point3::new(0.5, -1.5, 5.0), -Vector3::unit_z());
+        assert_eq!(below.intersect_aabb(&unit_box()), None);
+    }
+
+    #[test]
+    fn ray_through_the_triangle_hits_from_either_side() {
+        let [a, b, c] = triangle();
+        let front = Ray::new(Point3::new(0.25, 0.25, 2.0), -Vector3::unit_z());
+        assert_abs_diff_eq!(front.intersect_triangle(a, b, c).unwrap(), 2.0, epsilon = 1e-6);
+        let back = Ray::new(Point3::new(0.25, 0.25, -3.0), Vector3::unit_z());
+        assert_abs_diff_eq!(back.intersect_triangle(a, b, c).unwrap(), 3.0, epsilon = 1e-6);
+    }
+
+    #[test]
+    fn ray_through_an_edge_hits() {
+        let [a, b, c] = triangle();
+        let ray = Ray::new(Point3::new(0.5, 0.0, 1.0), -Vector3::unit_z());
+        assert_abs_diff_eq!(ray.intersect_triangle(a, b, c).unwrap(), 1.0, epsilon = 1e-6);
+        let hypotenuse = Ray::new(Point3::new(0.5, 0.5, 1.0), -Vector3::unit_z());
+        assert!(hypotenuse.intersect_triangle(a, b, c).is_some());
+        let outside = Ray::new(Point3::new(0.6, 0.6, 1.0), -Vector3::unit_z());
+        assert_eq!(outside.intersect_triangle(a, b, c), None);
+    }
+
+    #[test]
+    fn triangle_behind_the_ray_is_missed() {
+        let [a, b, c] = triangle();
+        let ray = Ray::new(Point3::new(0.25, 0.25, 2.0), Vector3::unit_z());
+        assert_eq!(ray.intersect_triangle(a, b, c), None);
+    }
+
+    #[test]
+    fn center_of_the_screen_looks_where_the_camera_does() {
+        let camera = OrbitCamera::new((1.0, 2.0, 3.0), 5.0, Deg(30.0), Deg(20.0), 1.0, 10.0);
+        let projection = Projection::new(800, 600, Deg(45.0), 0.1, 100.0);
+        let ray = Ray::from_cursor(PhysicalPosition::new(400.0, 300.0), PhysicalSize::new(800, 600), &camera, &projection).unwrap();
+        let forward = (camera.target - camera.position()).normalize();
+        assert_abs_diff_eq!(ray.direction, forward, epsilon = 1e-4);
+        // the ray starts on the near plane in front of the camera
+        assert_abs_diff_eq!(ray.origin.distance(camera.position()), 0.1, epsilon = 1e-3);
+    }
+}
\ No newline at end of file
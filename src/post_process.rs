@@ -0,0 +1,328 @@
+use bytemuck::{Pod, Zeroable};
+use wgpu::util::DeviceExt;
+use crate::grapics_context::GraphicsContext;
+use crate::texture::Texture;
+use crate::wgpu_helpers::{BlendMode, RenderPipelineBuilder};
+
+// number of progressively halved textures the bloom is blurred through
+const BLOOM_LEVELS: usize = 5;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Tonemapper {
+    // only clamps, bright areas clip like they did without HDR
+    None,
+    Reinhard,
+    Aces,
+}
+
+impl Tonemapper {
+    pub fn next(self) -> Self {
+        match self {
+            Tonemapper::None => Tonemapper::Reinhard,
+            Tonemapper::Reinhard => Tonemapper::Aces,
+            Tonemapper::Aces => Tonemapper::None,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PostProcessConfig {
+    pub exposure: f32,
+    pub tonemapper: Tonemapper,
+    pub bloom: bool,
+    // exposed brightness above which pixels start to glow
+    pub bloom_threshold: f32,
+    pub bloom_intensity: f32,
+    pub fxaa: bool,
+    pub vignette: bool,
+    pub vignette_strength: f32,
+}
+
+impl Default for PostProcessConfig {
+    fn default() -> Self {
+        Self {
+            exposure: 1.0,
+            tonemapper: Tonemapper::Aces,
+            bloom: true,
+            bloom_threshold: 1.0,
+            bloom_intensity: 0.1,
+            fxaa: false,
+            vignette: true,
+            vignette_strength: 0.3,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+struct PostUniform {
+    exposure: f32,
+    bloom_threshold: f32,
+    bloom_intensity: f32,
+    vignette_strength: f32,
+    tonemapper: u32,
+    _padding: [u32; 3],
+}
+
+impl PostUniform {
+    // disabled effects are turned into no-ops instead of separate shader variants
+    fn new(config: &PostProcessConfig) -> Self {
+        Self {
+            exposure: config.exposure,
+            bloom_threshold: config.bloom_threshold,
+            bloom_intensity: if config.bloom { config.bloom_intensity } else { 0.0 },
+            vignette_strength: if config.vignette { config.vignette_strength } else { 0.0 },
+            tonemapper: match config.tonemapper {
+                Tonemapper::None => 0,
+                Tonemapper::Reinhard => 1,
+                Tonemapper::Aces => 2,
+            },
+            _padding: [0; 3],
+        }
+    }
+}
+
+// textures sized to the frame and the bind groups reading them, recreated on resize
+struct Targets {
+    hdr: Texture,
+    ldr: Texture,
+    bloom: Vec<Texture>,
+    prefilter_bind_group: wgpu::BindGroup,
+    downsample_bind_groups: Vec<wgpu::BindGroup>,
+    upsample_bind_groups: Vec<wgpu::BindGroup>,
+    composite_bind_group: wgpu::BindGroup,
+    fxaa_bind_group: wgpu::BindGroup,
+}
+
+// Pages render the scene into hdr_view() and call render() to get it onto the frame
+pub struct PostProcess {
+    pub config: PostProcessConfig,
+    hdr_format: wgpu::TextureFormat,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    uniform_buffer: wgpu::Buffer,
+    // bound in place of the bloom texture by passes that don't read it
+    empty: Texture,
+    prefilter_pipeline: wgpu::RenderPipeline,
+    downsample_pipeline: wgpu::RenderPipeline,
+    upsample_pipeline: wgpu::RenderPipeline,
+    composite_pipeline: wgpu::RenderPipeline,
+    fxaa_pipeline: wgpu::RenderPipeline,
+    targets: Targets,
+}
+
+impl PostProcess {
+    pub fn new(graphics_context: &GraphicsContext, config: PostProcessConfig) -> Self {
+        let device = &graphics_context.device;
+        let hdr_format = graphics_context.hdr_format();
+        if hdr_format != Texture::HDR_FORMAT {
+            log::warn!("{:?} render targets are not supported, rendering without HDR", Texture::HDR_FORMAT);
+        }
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Post Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/post.wgsl").into()),
+        });
+
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                texture_entry(0),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                texture_entry(3),
+            ],
+            label: Some("post bind group layout"),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Post pipeline layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = |label, entry_point, format, blend| RenderPipelineBuilder::new(&shader)
+            .label(label)
+            .layout(&pipeline_layout)
+            .fragment_entry_point(Some(entry_point))
+            .color_target(format, blend)
+            .cull_mode(None)
+            .build(device);
+        let prefilter_pipeline = pipeline("Bloom prefilter pipeline", "fs_bloom_prefilter", hdr_format, BlendMode::Replace);
+        let downsample_pipeline = pipeline("Bloom downsample pipeline", "fs_bloom_downsample", hdr_format, BlendMode::Replace);
+        let upsample_pipeline = pipeline("Bloom upsample pipeline", "fs_bloom_upsample", hdr_format, BlendMode::Additive);
+        let composite_pipeline = pipeline("Composite pipeline", "fs_composite", graphics_context.format(), BlendMode::Replace);
+        let fxaa_pipeline = pipeline("FXAA pipeline", "fs_fxaa", graphics_context.format(), BlendMode::Replace);
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("post sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Post Buffer"),
+            contents: bytemuck::cast_slice(&[PostUniform::new(&config)]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let empty = Texture::create_render_target(device, 1, 1, hdr_format, "post empty texture");
+
+        let targets = Self::create_targets(graphics_context, hdr_format, &bind_group_layout, &sampler, &uniform_buffer, &empty);
+
+        Self {
+            config,
+            hdr_format,
+            bind_group_layout,
+            sampler,
+            uniform_buffer,
+            empty,
+            prefilter_pipeline,
+            downsample_pipeline,
+            upsample_pipeline,
+            composite_pipeline,
+            fxaa_pipeline,
+            targets,
+        }
+    }
+
+    fn create_targets(
+        graphics_context: &GraphicsContext,
+        hdr_format: wgpu::TextureFormat,
+        layout: &wgpu::BindGroupLayout,
+        sampler: &wgpu::Sampler,
+        uniform_buffer: &wgpu::Buffer,
+        empty: &Texture,
+    ) -> Targets {
+        let device = &graphics_context.device;
+        let config = graphics_context.config();
+
+        let hdr = Texture::create_render_target(device, config.width, config.height, hdr_format, "hdr_texture");
+        let ldr = Texture::create_render_target(device, config.width, config.height, graphics_context.format(), "ldr_texture");
+        let bloom: Vec<Texture> = (1..=BLOOM_LEVELS as u32)
+            .map(|level| Texture::create_render_target(device, config.width >> level, config.height >> level, hdr_format, "bloom_texture"))
+            .collect();
+
+        let bind_group = |source: &Texture, bloom: &Texture| device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&source.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(&bloom.view),
+                },
+            ],
+            label: Some("post bind group"),
+        });
+
+        Targets {
+            prefilter_bind_group: bind_group(&hdr, empty),
+            // level i is rendered from level i - 1 on the way down and added back into it on the way up
+            downsample_bind_groups: bloom.windows(2).map(|levels| bind_group(&levels[0], empty)).collect(),
+            upsample_bind_groups: bloom.windows(2).map(|levels| bind_group(&levels[1], empty)).collect(),
+            composite_bind_group: bind_group(&hdr, &bloom[0]),
+            fxaa_bind_group: bind_group(&ldr, empty),
+            hdr,
+            ldr,
+            bloom,
+        }
+    }
+
+    pub fn hdr_format(&self) -> wgpu::TextureFormat {
+        self.hdr_format
+    }
+
+    pub fn hdr_view(&self) -> &wgpu::TextureView {
+        &self.targets.hdr.view
+    }
+
+    pub fn resize(&mut self, graphics_context: &GraphicsContext) {
+        self.targets = Self::create_targets(
+            graphics_context,
+            self.hdr_format,
+            &self.bind_group_layout,
+            &self.sampler,
+            &self.uniform_buffer,
+            &self.empty,
+        );
+    }
+
+    // runs the enabled passes reading the HDR target, the last one writes into target
+    pub fn render(&self, graphics_context: &GraphicsContext, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView) {
+        graphics_context.queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[PostUniform::new(&self.config)]));
+        let targets = &self.targets;
+
+        if self.config.bloom {
+            Self::pass(encoder, "Bloom prefilter pass", &targets.bloom[0].view, false, &self.prefilter_pipeline, &targets.prefilter_bind_group);
+            for (level, bind_group) in targets.bloom[1..].iter().zip(&targets.downsample_bind_groups) {
+                Self::pass(encoder, "Bloom downsample pass", &level.view, false, &self.downsample_pipeline, bind_group);
+            }
+            for (level, bind_group) in targets.bloom[..BLOOM_LEVELS - 1].iter().zip(&targets.upsample_bind_groups).rev() {
+                Self::pass(encoder, "Bloom upsample pass", &level.view, true, &self.upsample_pipeline, bind_group);
+            }
+        }
+
+        let composite_target = if self.config.fxaa { &targets.ldr.view } else { target };
+        Self::pass(encoder, "Composite pass", composite_target, false, &self.composite_pipeline, &targets.composite_bind_group);
+        if self.config.fxaa {
+            Self::pass(encoder, "FXAA pass", target, false, &self.fxaa_pipeline, &targets.fxaa_bind_group);
+        }
+    }
+
+    // a full screen triangle, blend decides whether the existing contents are kept
+    fn pass(encoder: &mut wgpu::CommandEncoder, label: &str, view: &wgpu::TextureView, load: bool, pipeline: &wgpu::RenderPipeline, bind_group: &wgpu::BindGroup) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(label),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: if load { wgpu::LoadOp::Load } else { wgpu::LoadOp::Clear(wgpu::Color::BLACK) },
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,131 @@
+use std::cell::Cell;
+
+// Byte progress summed over every download since the last reset, which the router does
+// when it starts loading a page. Files loaded one after another keep adding to the same total.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct Progress {
+    pub loaded_bytes: u64,
+    pub total_bytes: u64,
+    pub downloads: usize,
+}
+
+impl Progress {
+    pub fn fraction(&self) -> f32 {
+        if self.total_bytes == 0 {
+            return 0.0;
+        }
+        (self.loaded_bytes as f64 / self.total_bytes as f64).min(1.0) as f32
+    }
+
+    pub fn is_loading(&self) -> bool {
+        self.downloads > 0
+    }
+}
+
+thread_local! {
+    static PROGRESS: Cell<Progress> = Cell::new(Progress::default());
+    // bumped on every reset, downloads of an earlier page load stop counting their bytes
+    static BATCH: Cell<u64> = const { Cell::new(0) };
+}
+
+pub fn current() -> Progress {
+    PROGRESS.get()
+}
+
+// starts a new aggregate, downloads still in flight are counted but their bytes aren't
+pub fn reset() {
+    BATCH.set(BATCH.get() + 1);
+    modify(|progress| {
+        progress.loaded_bytes = 0;
+        progress.total_bytes = 0;
+    });
+}
+
+fn modify(f: impl FnOnce(&mut Progress)) {
+    PROGRESS.with(|progress| {
+        let mut value = progress.get();
+        f(&mut value);
+        progress.set(value);
+    });
+}
+
+// counts towards the aggregate progress until dropped
+pub struct Download {
+    loaded: u64,
+    expected: u64,
+    batch: u64,
+}
+
+pub fn start_download(content_length: Option<u64>) -> Download {
+    let expected = content_length.unwrap_or(0);
+    modify(|progress| {
+        progress.downloads += 1;
+        progress.total_bytes += expected;
+    });
+    Download {
+        loaded: 0,
+        expected,
+        batch: BATCH.get(),
+    }
+}
+
+impl Download {
+    pub fn advance(&mut self, bytes: u64) {
+        // without a length, or with a compressed one, the total grows with the received bytes
+        let overflow = (self.loaded + bytes).saturating_sub(self.expected.max(self.loaded));
+        self.loaded += bytes;
+        if self.batch != BATCH.get() {
+            return;
+        }
+        modify(|progress| {
+            progress.loaded_bytes += bytes;
+            progress.total_bytes += overflow;
+        });
+    }
+}
+
+impl Drop for Download {
+    fn drop(&mut self) {
+        // a failed download shouldn't leave the bar stuck short of the end
+        let remaining = self.expected.saturating_sub(self.loaded);
+        let current_batch = self.batch == BATCH.get();
+        modify(|progress| {
+            if current_batch {
+                progress.loaded_bytes += remaining;
+            }
+            progress.downloads -= 1;
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sequential_downloads_accumulate() {
+        reset();
+        let mut obj = start_download(Some(100));
+        obj.advance(100);
+        drop(obj);
+        let mut mtl = start_download(Some(50));
+        mtl.advance(20);
+        assert_eq!(current(), Progress { loaded_bytes: 120, total_bytes: 150, downloads: 1 });
+        mtl.advance(30);
+        drop(mtl);
+        assert_eq!(current(), Progress { loaded_bytes: 150, total_bytes: 150, downloads: 0 });
+    }
+
+    #[test]
+    fn reset_ignores_earlier_downloads() {
+        reset();
+        let mut stale = start_download(Some(100));
+        reset();
+        let mut fresh = start_download(Some(10));
+        stale.advance(100);
+        fresh.advance(5);
+        drop(stale);
+        assert_eq!(current(), Progress { loaded_bytes: 5, total_bytes: 10, downloads: 1 });
+        drop(fresh);
+    }
+}
\ No newline at end of file
@@ -1,6 +1,7 @@
 use std::io::{BufReader, Cursor};
-use wgpu::util::DeviceExt;
+use cgmath::SquareMatrix;
 
+#[cfg(target_arch = "wasm32")]
 fn format_url(file_name: &str) -> reqwest::Url {
     let window = web_sys::window().unwrap();
     let location = window.location();
@@ -10,31 +11,71 @@ fn format_url(file_name: &str) -> reqwest::Url {
 }
 
 pub async fn load_string(file_name: &str) -> anyhow::Result<String> {
-    let url = format_url(file_name);
-    let text = reqwest::get(url)
-        .await?
-        .text()
-        .await?;
-    Ok(text)
+    let data = load_binary(file_name).await?;
+    String::from_utf8(data)
+        .map_err(|e| anyhow::anyhow!("{} is not valid UTF-8: {}", file_name, e))
 }
 
+// streamed so the loading screen can show how far along the download is
+#[cfg(target_arch = "wasm32")]
 pub async fn load_binary(file_name: &str) -> anyhow::Result<Vec<u8>> {
+    use futures_util::StreamExt;
     let url = format_url(file_name);
-    let data = reqwest::get(url)
-        .await?
-        .bytes()
-        .await?
-        .to_vec();
+    let response = reqwest::get(url).await?.error_for_status()?;
+    let mut download = crate::progress::start_download(response.content_length());
+    let mut data = Vec::with_capacity(response.content_length().unwrap_or(0) as usize);
+    let mut stream = response.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+        download.advance(chunk.len() as u64);
+        data.extend_from_slice(&chunk);
+    }
+    Ok(data)
+}
+
+// the desktop build reads the same files from a local assets directory
+#[cfg(not(target_arch = "wasm32"))]
+fn asset_path(file_name: &str) -> std::path::PathBuf {
+    std::path::Path::new("assets").join(file_name)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn load_binary(file_name: &str) -> anyhow::Result<Vec<u8>> {
+    let path = asset_path(file_name);
+    let mut download = crate::progress::start_download(std::fs::metadata(&path).ok().map(|m| m.len()));
+    let data = std::fs::read(&path)
+        .map_err(|e| anyhow::anyhow!("could not read {}: {}", path.display(), e))?;
+    download.advance(data.len() as u64);
     Ok(data)
 }
 
-pub async fn load_texture(file_name: &str, is_normal_map: bool, device: &wgpu::Device, queue: &wgpu::Queue) -> anyhow::Result<crate::texture::Texture> {
+pub async fn load_texture(
+    file_name: &str,
+    is_normal_map: bool,
+    mipmaps: &crate::texture::MipmapGenerator,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+) -> anyhow::Result<crate::texture::Texture> {
     let data = load_binary(file_name).await?;
-    crate::texture::Texture::from_bytes(device, queue, &data, file_name, is_normal_map)
+    crate::texture::Texture::from_bytes(device, queue, &data, file_name, is_normal_map, Some(mipmaps))
+}
+
+// a TTF or OTF font with a distance field atlas of the characters in charset
+pub async fn load_font(file_name: &str, charset: &str, device: &wgpu::Device, queue: &wgpu::Queue) -> anyhow::Result<crate::text::Font> {
+    let data = load_binary(file_name).await?;
+    crate::text::Font::from_bytes(device, queue, data, charset, file_name)
+}
+
+// an equirectangular .hdr (or any other image) panorama turned into a skybox and its lighting maps
+pub async fn load_environment(file_name: &str, graphics_context: &crate::grapics_context::GraphicsContext<'_>, intensity: f32) -> anyhow::Result<crate::environment::Environment> {
+    let data = load_binary(file_name).await?;
+    let image = image::load_from_memory(&data)?;
+    crate::environment::Environment::from_equirectangular(graphics_context, &image, intensity)
 }
 
 pub async fn load_model(
     file_name: &str,
+    assets: &crate::assets::AssetManager,
     device: &wgpu::Device,
     queue: &wgpu::Queue,
     layout: &wgpu::BindGroupLayout,
@@ -44,6 +85,12 @@ pub async fn load_model(
     let mut obj_reader = BufReader::new(obj_cursor);
 
 
+    // tobj only reports that the MTL couldn't be opened, keep the real reason around
+    let mtl_error = std::cell::RefCell::new(None);
+    let mtl_error = &mtl_error;
+
+    // the futures based loader wants an AsyncBufRead, the text is already in memory anyway
+    #[allow(deprecated)]
     let (models, obj_materials) = tobj::load_obj_buf_async(
         &mut obj_reader,
         &tobj::LoadOptions {
@@ -52,46 +99,66 @@ pub async fn load_model(
             ..Default::default()
         },
         |p| async move {
-            let mat_text = load_string(&p).await.unwrap();
-            tobj::load_mtl_buf(&mut BufReader::new(Cursor::new(mat_text)))
+            match load_string(&p).await {
+                Ok(mat_text) => tobj::load_mtl_buf(&mut BufReader::new(Cursor::new(mat_text))),
+                Err(e) => {
+                    *mtl_error.borrow_mut() = Some(e);
+                    Err(tobj::LoadError::OpenFileFailed)
+                }
+            }
         },
     ).await?;
+    let obj_materials = obj_materials
+        .map_err(|e| mtl_error.take().unwrap_or_else(|| e.into()))?;
 
     let mut materials = Vec::new();
-    for m in obj_materials? {
+    for m in obj_materials {
+        // PBR extension to MTL: Pm, Pr, Ke and map_Ke
+        let parse_floats = |key: &str| m.unknown_param.get(key)
+            .map(|v| v.split_whitespace().filter_map(|f| f.parse::<f32>().ok()).collect::<Vec<_>>());
+        let emissive_map = m.unknown_param.get("map_Ke").cloned();
+
+        // like glTF, a texture replaces the constant color instead of being tinted by it
+        let base_color = match m.diffuse_texture {
+            Some(_) => [1.0; 3],
+            None => m.diffuse.unwrap_or([1.0; 3]),
+        };
+        let emissive_factor = match parse_floats("Ke") {
+            Some(ke) if ke.len() >= 3 => [ke[0], ke[1], ke[2]],
+            _ if emissive_map.is_some() => [1.0; 3],
+            _ => [0.0; 3],
+        };
+        let roughness_factor = parse_floats("Pr").and_then(|pr| pr.first().copied())
+            .or(m.shininess.map(|ns| (2.0 / (ns + 2.0)).sqrt()))
+            .unwrap_or(1.0);
+        let uniform = crate::model::MaterialUniform {
+            base_color_factor: [base_color[0], base_color[1], base_color[2], m.dissolve.unwrap_or(1.0)],
+            emissive_factor,
+            metallic_factor: parse_floats("Pm").and_then(|pm| pm.first().copied()).unwrap_or(0.0),
+            roughness_factor,
+            occlusion_strength: 1.0,
+            normal_scale: 1.0,
+            _padding: 0.0,
+        };
+
+        // shared between materials through the asset manager, including the fallbacks
         let texture_name = m.diffuse_texture.unwrap_or("default_diffuse.qoi".to_string());
-        let diffuse_texture = load_texture(&texture_name, true, device, queue).await?;
+        let base_color = assets.texture(&texture_name, false, device, queue).await?;
         let texture_name = m.normal_texture.unwrap_or("default_normal.qoi".to_string());
-        let normal_texture = load_texture(&texture_name, true, device, queue).await?;
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&diffuse_texture.view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&diffuse_texture.sampler),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 2,
-                    resource: wgpu::BindingResource::TextureView(&normal_texture.view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 3,
-                    resource: wgpu::BindingResource::Sampler(&normal_texture.sampler),
-                },
-            ],
-            label: None,
-        });
+        let normal = assets.texture(&texture_name, true, device, queue).await?;
+        let emissive = match emissive_map {
+            Some(texture_name) => assets.texture(&texture_name, false, device, queue).await?,
+            None => assets.color_texture([255; 4], false, device, queue).await?,
+        };
+        let textures = crate::model::MaterialTextures {
+            base_color,
+            normal,
+            metallic_roughness: assets.color_texture([255; 4], true, device, queue).await?,
+            occlusion: assets.color_texture([255; 4], true, device, queue).await?,
+            emissive,
+        };
 
-        materials.push(crate::model::Material {
-            name: m.name,
-            diffuse_texture,
-            normal_texture,
-            bind_group,
-        })
+        materials.push(crate::model::Material::new(device, &m.name, textures, uniform, layout))
     }
 
     let meshes = models
@@ -120,77 +187,495 @@ pub async fn load_model(
                         },
                         tangent: [0.0; 3],
                         bitangent: [0.0; 3],
+                        joints: [0; 4],
+                        weights: [0.0; 4],
                     }
                 })
                 .collect::<Vec<_>>();
 
-            let indices = &m.mesh.indices;
-            let mut triangles_included = vec![0; vertices.len()];
+            calculate_tangents(&mut vertices, &m.mesh.indices);
 
-            // Calculate tangents and bitangets.
-            for c in indices.chunks_exact(3) {
-                let v0 = vertices[c[0] as usize];
-                let v1 = vertices[c[1] as usize];
-                let v2 = vertices[c[2] as usize];
+            crate::model::Mesh::new(device, file_name, &vertices, &m.mesh.indices, m.mesh.material_id.unwrap_or(0))
+        })
+        .collect::<Vec<_>>();
 
-                let pos0: cgmath::Vector3<_> = v0.position.into();
-                let pos1: cgmath::Vector3<_> = v1.position.into();
-                let pos2: cgmath::Vector3<_> = v2.position.into();
+    Ok(crate::model::Model {
+        meshes,
+        materials,
+        skeleton: None,
+        animations: Vec::new(),
+    })
+}
 
-                let uv0: cgmath::Vector2<_> = v0.tex_coords.into();
-                let uv1: cgmath::Vector2<_> = v1.tex_coords.into();
-                let uv2: cgmath::Vector2<_> = v2.tex_coords.into();
+fn calculate_tangents(vertices: &mut [crate::model::ModelVertex], indices: &[u32]) {
+    let mut triangles_included = vec![0; vertices.len()];
 
-                let delta_pos1 = pos1 - pos0;
-                let delta_pos2 = pos2 - pos0;
+    // Calculate tangents and bitangets.
+    for c in indices.chunks_exact(3) {
+        let v0 = vertices[c[0] as usize];
+        let v1 = vertices[c[1] as usize];
+        let v2 = vertices[c[2] as usize];
 
-                let delta_uv1 = uv1 - uv0;
-                let delta_uv2 = uv2 - uv0;
+        let pos0: cgmath::Vector3<_> = v0.position.into();
+        let pos1: cgmath::Vector3<_> = v1.position.into();
+        let pos2: cgmath::Vector3<_> = v2.position.into();
 
-                let r = 1.0 / (delta_uv1.x * delta_uv2.y - delta_uv1.y * delta_uv2.x);
-                let tangent = (delta_pos1 * delta_uv2.y - delta_pos2 * delta_uv1.y) * r;
-                let bitangent = (delta_pos2 * delta_uv1.x - delta_pos1 * delta_uv2.x) * -r;
+        let uv0: cgmath::Vector2<_> = v0.tex_coords.into();
+        let uv1: cgmath::Vector2<_> = v1.tex_coords.into();
+        let uv2: cgmath::Vector2<_> = v2.tex_coords.into();
 
-                vertices[c[0] as usize].tangent = (tangent + cgmath::Vector3::from(vertices[c[0] as usize].tangent)).into();
-                vertices[c[1] as usize].tangent = (tangent + cgmath::Vector3::from(vertices[c[1] as usize].tangent)).into();
-                vertices[c[2] as usize].tangent = (tangent + cgmath::Vector3::from(vertices[c[2] as usize].tangent)).into();
-                vertices[c[0] as usize].bitangent = (bitangent + cgmath::Vector3::from(vertices[c[0] as usize].bitangent)).into();
-                vertices[c[1] as usize].bitangent = (bitangent + cgmath::Vector3::from(vertices[c[1] as usize].bitangent)).into();
-                vertices[c[2] as usize].bitangent = (bitangent + cgmath::Vector3::from(vertices[c[2] as usize].bitangent)).into();
+        let delta_pos1 = pos1 - pos0;
+        let delta_pos2 = pos2 - pos0;
 
-                triangles_included[c[0] as usize] += 1;
-                triangles_included[c[1] as usize] += 1;
-                triangles_included[c[2] as usize] += 1;
-            }
+        let delta_uv1 = uv1 - uv0;
+        let delta_uv2 = uv2 - uv0;
+
+        let r = 1.0 / (delta_uv1.x * delta_uv2.y - delta_uv1.y * delta_uv2.x);
+        let tangent = (delta_pos1 * delta_uv2.y - delta_pos2 * delta_uv1.y) * r;
+        let bitangent = (delta_pos2 * delta_uv1.x - delta_pos1 * delta_uv2.x) * -r;
+
+        vertices[c[0] as usize].tangent = (tangent + cgmath::Vector3::from(vertices[c[0] as usize].tangent)).into();
+        vertices[c[1] as usize].tangent = (tangent + cgmath::Vector3::from(vertices[c[1] as usize].tangent)).into();
+        vertices[c[2] as usize].tangent = (tangent + cgmath::Vector3::from(vertices[c[2] as usize].tangent)).into();
+        vertices[c[0] as usize].bitangent = (bitangent + cgmath::Vector3::from(vertices[c[0] as usize].bitangent)).into();
+        vertices[c[1] as usize].bitangent = (bitangent + cgmath::Vector3::from(vertices[c[1] as usize].bitangent)).into();
+        vertices[c[2] as usize].bitangent = (bitangent + cgmath::Vector3::from(vertices[c[2] as usize].bitangent)).into();
+
+        triangles_included[c[0] as usize] += 1;
+        triangles_included[c[1] as usize] += 1;
+        triangles_included[c[2] as usize] += 1;
+    }
+
+    // average tangents
+    for (i, n) in triangles_included.into_iter().enumerate() {
+        let denom = 1.0 / n as f32;
+        let v = &mut vertices[i];
+        v.tangent = (cgmath::Vector3::from(v.tangent) * denom).into();
+        v.bitangent = (cgmath::Vector3::from(v.bitangent) * denom).into();
+    }
+}
+
+// resolves a uri found inside a gltf file relative to that file
+fn relative_path(file_name: &str, uri: &str) -> String {
+    match file_name.rfind('/') {
+        Some(i) => format!("{}{}", &file_name[..=i], uri),
+        None => uri.to_string(),
+    }
+}
 
-            // average tangents
-            for (i, n) in triangles_included.into_iter().enumerate() {
-                let denom = 1.0 / n as f32;
-                let v = &mut vertices[i];
-                v.tangent = (cgmath::Vector3::from(v.tangent) * denom).into();
-                v.bitangent = (cgmath::Vector3::from(v.bitangent) * denom).into();
+async fn load_gltf_uri(file_name: &str, uri: &str) -> anyhow::Result<Vec<u8>> {
+    use base64::Engine;
+    match uri.strip_prefix("data:") {
+        Some(data) => {
+            let (header, payload) = data.split_once(',')
+                .ok_or_else(|| anyhow::anyhow!("malformed data uri in {}", file_name))?;
+            if !header.ends_with(";base64") {
+                anyhow::bail!("only base64 data uris are supported, found {:?} in {}", header, file_name);
             }
+            Ok(base64::engine::general_purpose::STANDARD.decode(payload)?)
+        }
+        None => load_binary(&relative_path(file_name, uri)).await,
+    }
+}
 
-            let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some(&format!("{:?} Vertex Buffer", file_name)),
-                contents: bytemuck::cast_slice(&vertices),
-                usage: wgpu::BufferUsages::VERTEX,
-            });
-            let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some(&format!("{:?} Index Buffer", file_name)),
-                contents: bytemuck::cast_slice(&m.mesh.indices),
-                usage: wgpu::BufferUsages::INDEX,
-            });
+// Loads a .gltf or .glb file, producing one model per gltf mesh together with
+// the instances of every node in the default scene that references it.
+pub async fn load_gltf(
+    file_name: &str,
+    assets: &crate::assets::AssetManager,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    layout: &wgpu::BindGroupLayout,
+) -> anyhow::Result<Vec<(crate::model::Model, Vec<crate::model::Instance>)>> {
+    let gltf_data = load_binary(file_name).await?;
+    let gltf = gltf::Gltf::from_slice(&gltf_data)?;
 
-            crate::model::Mesh {
-                name: file_name.to_string(),
-                vertex_buffer,
-                index_buffer,
-                num_elements: m.mesh.indices.len() as u32,
-                material: m.mesh.material_id.unwrap_or(0),
+    let mut buffers = Vec::new();
+    for buffer in gltf.buffers() {
+        let data = match buffer.source() {
+            gltf::buffer::Source::Bin => gltf.blob.clone()
+                .ok_or_else(|| anyhow::anyhow!("{} references a missing binary chunk", file_name))?,
+            gltf::buffer::Source::Uri(uri) => load_gltf_uri(file_name, uri).await?,
+        };
+        buffers.push(data);
+    }
+
+    let mut images = Vec::new();
+    for image in gltf.images() {
+        let data = match image.source() {
+            gltf::image::Source::View { view, .. } => {
+                let buffer = &buffers[view.buffer().index()];
+                buffer[view.offset()..view.offset() + view.length()].to_vec()
             }
+            gltf::image::Source::Uri { uri, .. } => load_gltf_uri(file_name, uri).await?,
+        };
+        images.push(data);
+    }
+
+    // missing maps are replaced with a single texel that leaves the factors unchanged
+    let white = assets.color_texture([255; 4], false, device, queue).await?;
+    let white_linear = assets.color_texture([255; 4], true, device, queue).await?;
+    let flat_normal = assets.color_texture([128, 128, 255, 255], true, device, queue).await?;
+    // identical to the renderer's own layout, which makes the bind groups interchangeable
+    let morph_layout = crate::morph::MorphTargets::create_bind_group_layout(device);
+
+    // materials referencing the same image share one texture
+    let mut textures = std::collections::HashMap::new();
+    let mut load_gltf_texture = |texture: Option<gltf::texture::Texture>, is_normal_map: bool, default: &crate::assets::Handle<crate::texture::Texture>| {
+        let Some(texture) = texture else {
+            return Ok(default.clone());
+        };
+        let key = (texture.source().index(), texture.sampler().index(), is_normal_map);
+        if let Some(texture) = textures.get(&key) {
+            return Ok(std::rc::Rc::clone(texture));
+        }
+        let texture = crate::texture::Texture::from_bytes(device, queue, &images[key.0], file_name, is_normal_map, Some(assets.mipmaps()))?
+            .with_sampler(device, &gltf_sampler(&texture.sampler()));
+        let texture = std::rc::Rc::new(texture);
+        textures.insert(key, texture.clone());
+        anyhow::Ok(texture)
+    };
+
+    // flatten the node hierarchy into world transforms per mesh
+    let mut mesh_instances = vec![Vec::new(); gltf.meshes().len()];
+    // the first skin a mesh is used with, skinned meshes are placed by their joints instead of their node
+    let mut mesh_skins = vec![None; gltf.meshes().len()];
+    let mut node_worlds = vec![cgmath::Matrix4::identity(); gltf.nodes().len()];
+    let mut node_parents = vec![None; gltf.nodes().len()];
+    let scene = gltf.default_scene()
+        .or_else(|| gltf.scenes().next())
+        .ok_or_else(|| anyhow::anyhow!("{} contains no scenes", file_name))?;
+    let mut stack = scene.nodes()
+        .map(|node| {
+            let local = cgmath::Matrix4::from(node.transform().matrix());
+            (node, local)
         })
         .collect::<Vec<_>>();
+    while let Some((node, world)) = stack.pop() {
+        node_worlds[node.index()] = world;
+        if let Some(mesh) = node.mesh() {
+            match node.skin() {
+                Some(skin) => {
+                    mesh_skins[mesh.index()].get_or_insert(skin);
+                    mesh_instances[mesh.index()].push(cgmath::Matrix4::identity());
+                }
+                None => mesh_instances[mesh.index()].push(world),
+            }
+        }
+        for child in node.children() {
+            node_parents[child.index()] = Some(node.index());
+            let local = cgmath::Matrix4::from(child.transform().matrix());
+            stack.push((child, world * local));
+        }
+    }
+
+    let mut result = Vec::new();
+    for mesh in gltf.meshes() {
+        let mut meshes = Vec::new();
+        let mut materials = Vec::new();
+        let mut material_indices = std::collections::HashMap::new();
+
+        for primitive in mesh.primitives() {
+            if primitive.mode() != gltf::mesh::Mode::Triangles {
+                log::warn!("skipping non triangle primitive in {}", file_name);
+                continue;
+            }
+
+            let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(Vec::as_slice));
+            let positions = reader.read_positions()
+                .ok_or_else(|| anyhow::anyhow!("primitive without positions in {}", file_name))?
+                .collect::<Vec<_>>();
+            let normals = reader.read_normals()
+                .map(|n| n.collect::<Vec<_>>())
+                .unwrap_or_else(|| vec![[0.0; 3]; positions.len()]);
+            let tex_coords = reader.read_tex_coords(0)
+                .map(|t| t.into_f32().collect::<Vec<_>>())
+                .unwrap_or_else(|| vec![[0.0; 2]; positions.len()]);
+            let tangents = reader.read_tangents().map(|t| t.collect::<Vec<_>>());
+            let joints = reader.read_joints(0)
+                .map(|j| j.into_u16().collect::<Vec<_>>())
+                .unwrap_or_else(|| vec![[0; 4]; positions.len()]);
+            let weights = reader.read_weights(0)
+                .map(|w| w.into_f32().collect::<Vec<_>>())
+                .unwrap_or_else(|| vec![[0.0; 4]; positions.len()]);
+            let indices = reader.read_indices()
+                .map(|i| i.into_u32().collect::<Vec<_>>())
+                .unwrap_or_else(|| (0..positions.len() as u32).collect());
+
+            let mut vertices = (0..positions.len())
+                .map(|i| {
+                    let (tangent, bitangent) = match &tangents {
+                        Some(tangents) => {
+                            let normal = cgmath::Vector3::from(normals[i]);
+                            let tangent = cgmath::Vector3::new(tangents[i][0], tangents[i][1], tangents[i][2]);
+                            (tangent.into(), (normal.cross(tangent) * tangents[i][3]).into())
+                        }
+                        None => ([0.0; 3], [0.0; 3]),
+                    };
+                    crate::model::ModelVertex {
+                        position: positions[i],
+                        tex_coords: tex_coords[i],
+                        normal: normals[i],
+                        tangent,
+                        bitangent,
+                        joints: joints[i],
+                        weights: normalize_weights(weights[i]),
+                    }
+                })
+                .collect::<Vec<_>>();
+
+            if tangents.is_none() {
+                calculate_tangents(&mut vertices, &indices);
+            }
+
+            // primitives of the same mesh can share a material
+            let gltf_material = primitive.material();
+            let material = match material_indices.get(&gltf_material.index()) {
+                Some(&material) => material,
+                None => {
+                    let name = gltf_material.name().unwrap_or("default");
+                    let pbr = gltf_material.pbr_metallic_roughness();
+                    let normal_texture = gltf_material.normal_texture();
+                    let occlusion_texture = gltf_material.occlusion_texture();
+                    let uniform = crate::model::MaterialUniform {
+                        base_color_factor: pbr.base_color_factor(),
+                        emissive_factor: gltf_material.emissive_factor(),
+                        metallic_factor: pbr.metallic_factor(),
+                        roughness_factor: pbr.roughness_factor(),
+                        occlusion_strength: occlusion_texture.as_ref().map_or(1.0, |t| t.strength()),
+                        normal_scale: normal_texture.as_ref().map_or(1.0, |t| t.scale()),
+                        _padding: 0.0,
+                    };
+                    let textures = crate::model::MaterialTextures {
+                        base_color: load_gltf_texture(pbr.base_color_texture().map(|t| t.texture()), false, &white)?,
+                        normal: load_gltf_texture(normal_texture.map(|t| t.texture()), true, &flat_normal)?,
+                        metallic_roughness: load_gltf_texture(pbr.metallic_roughness_texture().map(|t| t.texture()), true, &white_linear)?,
+                        occlusion: load_gltf_texture(occlusion_texture.map(|t| t.texture()), true, &white_linear)?,
+                        emissive: load_gltf_texture(gltf_material.emissive_texture().map(|t| t.texture()), false, &white)?,
+                    };
+                    materials.push(crate::model::Material::new(device, name, textures, uniform, layout));
+                    material_indices.insert(gltf_material.index(), materials.len() - 1);
+                    materials.len() - 1
+                }
+            };
+
+            let name = mesh.name().unwrap_or(file_name);
+            let mut gltf_mesh = crate::model::Mesh::new(device, name, &vertices, &indices, material);
+            let targets = reader.read_morph_targets()
+                .map(|(positions, normals, _)| crate::morph::MorphTarget {
+                    positions: positions.map(|p| p.collect()).unwrap_or_else(|| vec![[0.0; 3]; vertices.len()]),
+                    normals: normals.map(|n| n.collect()).unwrap_or_default(),
+                })
+                .collect::<Vec<_>>();
+            if !targets.is_empty() {
+                let default_weights = mesh.weights().unwrap_or_default();
+                gltf_mesh.set_morph_targets(crate::morph::MorphTargets::new(device, queue, &morph_layout, vertices.len(), &targets, default_weights, name)?);
+            }
+            meshes.push(gltf_mesh);
+        }
+
+        let instances = mesh_instances[mesh.index()].iter()
+            .map(|&world| crate::model::Instance::from_matrix(world))
+            .collect();
+        let skin = mesh_skins[mesh.index()].as_ref();
+        let skeleton = skin.map(|skin| load_gltf_skeleton(skin, &buffers, &node_worlds, &node_parents));
+        let animated = skin.is_some() || meshes.iter().any(|mesh| mesh.morph_targets.is_some());
+        let animations = if animated { load_gltf_animations(&gltf, &mesh, skin, &buffers) } else { Vec::new() };
+        result.push((crate::model::Model { meshes, materials, skeleton, animations }, instances));
+    }
+
+    Ok(result)
+}
+
+// exporters don't always make them add up to 1
+// glTF leaves the filters up to the renderer when they're missing, trilinear like other textures
+fn gltf_sampler(sampler: &gltf::texture::Sampler) -> wgpu::SamplerDescriptor<'static> {
+    use gltf::texture::{MagFilter, MinFilter, WrappingMode};
+    let address_mode = |mode| match mode {
+        WrappingMode::ClampToEdge => wgpu::AddressMode::ClampToEdge,
+        WrappingMode::MirroredRepeat => wgpu::AddressMode::MirrorRepeat,
+        WrappingMode::Repeat => wgpu::AddressMode::Repeat,
+    };
+    let mag_filter = match sampler.mag_filter() {
+        Some(MagFilter::Nearest) => wgpu::FilterMode::Nearest,
+        Some(MagFilter::Linear) | None => wgpu::FilterMode::Linear,
+    };
+    let (min_filter, mipmap_filter) = match sampler.min_filter() {
+        Some(MinFilter::Nearest | MinFilter::NearestMipmapNearest) => (wgpu::FilterMode::Nearest, wgpu::FilterMode::Nearest),
+        Some(MinFilter::Linear | MinFilter::LinearMipmapNearest) => (wgpu::FilterMode::Linear, wgpu::FilterMode::Nearest),
+        Some(MinFilter::NearestMipmapLinear) => (wgpu::FilterMode::Nearest, wgpu::FilterMode::Linear),
+        Some(MinFilter::LinearMipmapLinear) | None => (wgpu::FilterMode::Linear, wgpu::FilterMode::Linear),
+    };
+    let linear = [mag_filter, min_filter, mipmap_filter].iter().all(|&filter| filter == wgpu::FilterMode::Linear);
+    wgpu::SamplerDescriptor {
+        address_mode_u: address_mode(sampler.wrap_s()),
+        address_mode_v: address_mode(sampler.wrap_t()),
+        address_mode_w: wgpu::AddressMode::ClampToEdge,
+        mag_filter,
+        min_filter,
+        mipmap_filter,
+        // wgpu only allows anisotropy when every filter is linear
+        anisotropy_clamp: if linear { 16 } else { 1 },
+        ..Default::default()
+    }
+}
+
+fn normalize_weights(weights: [f32; 4]) -> [f32; 4] {
+    let total: f32 = weights.iter().sum();
+    if total > 0.0 {
+        weights.map(|weight| weight / total)
+    } else {
+        weights
+    }
+}
+
+fn load_gltf_skeleton(
+    skin: &gltf::Skin,
+    buffers: &[Vec<u8>],
+    node_worlds: &[cgmath::Matrix4<f32>],
+    node_parents: &[Option<usize>],
+) -> crate::animation::Skeleton {
+    let joint_nodes: Vec<usize> = skin.joints().map(|joint| joint.index()).collect();
+    let joints = skin.joints()
+        .map(|joint| {
+            let (translation, [x, y, z, w], scale) = joint.transform().decomposed();
+            crate::animation::Joint {
+                name: joint.name().unwrap_or_default().to_string(),
+                parent: node_parents[joint.index()].and_then(|parent| joint_nodes.iter().position(|&node| node == parent)),
+                rest: crate::model::Instance {
+                    position: translation.into(),
+                    rotation: cgmath::Quaternion::new(w, x, y, z),
+                    scale: scale.into(),
+                },
+            }
+        })
+        .collect::<Vec<crate::animation::Joint>>();
+    // everything above the first root joint, assumed to be the same for all of them
+    let root = joints.iter()
+        .zip(&joint_nodes)
+        .find(|(joint, _)| joint.parent.is_none())
+        .and_then(|(_, &node)| node_parents[node])
+        .map_or(cgmath::Matrix4::identity(), |parent| node_worlds[parent]);
+    let inverse_bind_matrices = skin.reader(|buffer| buffers.get(buffer.index()).map(Vec::as_slice))
+        .read_inverse_bind_matrices()
+        .map(|matrices| matrices.map(cgmath::Matrix4::from).collect())
+        .unwrap_or_else(|| vec![cgmath::Matrix4::identity(); joints.len()]);
+    crate::animation::Skeleton::new(joints, inverse_bind_matrices, root)
+}
+
+// cubic splines store in and out tangents around every value, only the values are kept
+fn spline_values<T: Clone>(values: Vec<T>, interpolation: gltf::animation::Interpolation) -> Vec<T> {
+    match interpolation {
+        gltf::animation::Interpolation::CubicSpline => values.chunks_exact(3).map(|key| key[1].clone()).collect(),
+        _ => values,
+    }
+}
 
-    Ok(crate::model::Model { meshes, materials })
+// every animation that moves at least one of the skin's joints or the mesh's morph target weights
+fn load_gltf_animations(gltf: &gltf::Gltf, mesh: &gltf::Mesh, skin: Option<&gltf::Skin>, buffers: &[Vec<u8>]) -> Vec<crate::animation::AnimationClip> {
+    use crate::animation::{AnimationClip, Channel, Interpolation, Keyframes, WeightsChannel};
+    let joint_nodes: Vec<usize> = skin.map(|skin| skin.joints().map(|joint| joint.index()).collect()).unwrap_or_default();
+    let mut clips = Vec::new();
+    for animation in gltf.animations() {
+        let mut channels = Vec::new();
+        let mut weights = None;
+        for channel in animation.channels() {
+            let node = channel.target().node();
+            let joint = joint_nodes.iter().position(|&joint| joint == node.index());
+            let animates_mesh = node.mesh().is_some_and(|node_mesh| node_mesh.index() == mesh.index());
+            if joint.is_none() && !animates_mesh {
+                continue;
+            }
+            let reader = channel.reader(|buffer| buffers.get(buffer.index()).map(Vec::as_slice));
+            let (Some(times), Some(outputs)) = (reader.read_inputs(), reader.read_outputs()) else {
+                continue;
+            };
+            let times: Vec<f32> = times.collect();
+            let interpolation = match channel.sampler().interpolation() {
+                gltf::animation::Interpolation::Step => Interpolation::Step,
+                _ => Interpolation::Linear,
+            };
+            let spline = channel.sampler().interpolation();
+            let keyframes = match outputs {
+                gltf::animation::util::ReadOutputs::Translations(translations) => Keyframes::Translation(
+                    spline_values(translations.map(cgmath::Vector3::from).collect(), spline)
+                ),
+                gltf::animation::util::ReadOutputs::Rotations(rotations) => Keyframes::Rotation(
+                    spline_values(rotations.into_f32().map(|[x, y, z, w]| cgmath::Quaternion::new(w, x, y, z)).collect(), spline)
+                ),
+                gltf::animation::util::ReadOutputs::Scales(scales) => Keyframes::Scale(
+                    spline_values(scales.map(cgmath::Vector3::from).collect(), spline)
+                ),
+                gltf::animation::util::ReadOutputs::MorphTargetWeights(values) => {
+                    // all the weights of a keyframe are stored one after the other
+                    let values: Vec<f32> = values.into_f32().collect();
+                    let per_keyframe = values.len() / times.len().max(1);
+                    let per_keyframe = if spline == gltf::animation::Interpolation::CubicSpline { per_keyframe / 3 } else { per_keyframe };
+                    if animates_mesh && weights.is_none() && per_keyframe > 0 {
+                        let keyframes = values.chunks_exact(per_keyframe).map(<[f32]>::to_vec).collect();
+                        weights = Some(WeightsChannel {
+                            interpolation,
+                            weights: spline_values(keyframes, spline),
+                            times,
+                        });
+                    }
+                    continue;
+                }
+            };
+            let Some(joint) = joint else {
+                continue;
+            };
+            channels.push(Channel {
+                joint,
+                interpolation,
+                times,
+                keyframes,
+            });
+        }
+        if channels.is_empty() && weights.is_none() {
+            continue;
+        }
+        let duration = channels.iter()
+            .map(|channel| &channel.times)
+            .chain(weights.as_ref().map(|weights| &weights.times))
+            .filter_map(|times| times.last())
+            .copied()
+            .fold(0.0, f32::max);
+        clips.push(AnimationClip {
+            name: animation.name().unwrap_or_default().to_string(),
+            duration,
+            channels,
+            weights,
+        });
+    }
+    clips
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gltf_samplers_repeat_by_default_and_keep_their_filters() {
+        let json = r#"{
+            "asset": { "version": "2.0" },
+            "samplers": [
+                {},
+                { "wrapS": 33071, "wrapT": 33648, "magFilter": 9728, "minFilter": 9984 }
+            ]
+        }"#;
+        let gltf = gltf::Gltf::from_slice(json.as_bytes()).unwrap();
+        let samplers: Vec<_> = gltf.samplers().map(|sampler| gltf_sampler(&sampler)).collect();
+
+        assert_eq!(samplers[0].address_mode_u, wgpu::AddressMode::Repeat);
+        assert_eq!(samplers[0].address_mode_v, wgpu::AddressMode::Repeat);
+        assert_eq!(samplers[0].mipmap_filter, wgpu::FilterMode::Linear);
+        assert_eq!(samplers[0].anisotropy_clamp, 16);
+
+        assert_eq!(samplers[1].address_mode_u, wgpu::AddressMode::ClampToEdge);
+        assert_eq!(samplers[1].address_mode_v, wgpu::AddressMode::MirrorRepeat);
+        assert_eq!(samplers[1].mag_filter, wgpu::FilterMode::Nearest);
+        assert_eq!(samplers[1].min_filter, wgpu::FilterMode::Nearest);
+        assert_eq!(samplers[1].anisotropy_clamp, 1);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,167 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::closure::Closure;
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::JsCast;
+use crate::error::Error;
+use crate::grapics_context::GraphicsContext;
+use crate::runnable::Runnable;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Route {
+    Home,
+    Projects,
+    About,
+}
+
+impl Route {
+    // the hash takes precedence, GitHub pages can only serve the root path
+    pub fn from_location(path: &str, hash: &str) -> Self {
+        let route = hash.strip_prefix('#')
+            .filter(|hash| !hash.is_empty())
+            .unwrap_or(path);
+        match route.trim_matches('/') {
+            "" | "home" | "index.html" => Route::Home,
+            "projects" => Route::Projects,
+            "about" => Route::About,
+            unknown => {
+                log::warn!("unknown route {:?}, falling back to home", unknown);
+                Route::Home
+            }
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn current() -> Self {
+        let location = web_sys::window().unwrap().location();
+        Self::from_location(
+            &location.pathname().unwrap_or_default(),
+            &location.hash().unwrap_or_default(),
+        )
+    }
+
+    // the desktop build takes the route as its first argument, e.g. `personal_page '#/about'`
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn current() -> Self {
+        let route = std::env::args().nth(1).unwrap_or_default();
+        Self::from_location("", &route)
+    }
+
+    async fn load(self, graphics_context: &GraphicsContext<'_>) -> Result<Box<dyn Runnable>, Error> {
+        // every section of the site is still a work in progress
+        match self {
+            Route::Home | Route::Projects | Route::About => Ok(Box::new(crate::wip_page::WipPage::new(graphics_context).await?)),
+        }
+    }
+}
+
+// filled in by the background load once the page is ready
+type LoadedPage = Rc<RefCell<Option<(Route, Result<Box<dyn Runnable>, Error>)>>>;
+
+// Owns the current page and swaps it out when the URL changes, without reloading the WASM
+pub struct Router {
+    graphics_context: Rc<GraphicsContext<'static>>,
+    page: Option<Box<dyn Runnable>>,
+    page_route: Option<Route>,
+    requested_route: Option<Route>,
+    loaded_page: LoadedPage,
+    location_changed: Rc<Cell<bool>>,
+    #[cfg(target_arch = "wasm32")]
+    _location_listener: Closure<dyn FnMut()>,
+}
+
+impl Router {
+    pub fn new(graphics_context: Rc<GraphicsContext<'static>>) -> Self {
+        // start out as changed so the first poll loads the initial page
+        let location_changed = Rc::new(Cell::new(true));
+
+        #[cfg(target_arch = "wasm32")]
+        let location_listener = {
+            let flag = location_changed.clone();
+            // the event loop may be asleep in on-demand mode, a redraw wakes it up to poll the router
+            let redraw_window = graphics_context.window;
+            let location_listener = Closure::<dyn FnMut()>::new(move || {
+                flag.set(true);
+                if let Some(window) = redraw_window {
+                    window.request_redraw();
+                }
+            });
+            let window = web_sys::window().unwrap();
+            for event in ["hashchange", "popstate"] {
+                if let Err(e) = window.add_event_listener_with_callback(event, location_listener.as_ref().unchecked_ref()) {
+                    log::error!("could not listen for {}: {:?}", event, e);
+                }
+            }
+            location_listener
+        };
+
+        Self {
+            graphics_context,
+            page: None,
+            page_route: None,
+            requested_route: None,
+            loaded_page: Rc::new(RefCell::new(None)),
+            location_changed,
+            #[cfg(target_arch = "wasm32")]
+            _location_listener: location_listener,
+        }
+    }
+
+    pub fn page(&mut self) -> Option<&mut (dyn Runnable + 'static)> {
+        self.page.as_deref_mut()
+    }
+
+    // true from a navigation until the new page replaces the old one
+    pub fn is_loading(&self) -> bool {
+        self.page_route != self.requested_route
+    }
+
+    // starts loading a page after a navigation and switches to it once it's ready,
+    // a page that fails to load shows its error until the next navigation
+    pub fn poll(&mut self) {
+        if self.location_changed.replace(false) {
+            let route = Route::current();
+            if self.requested_route != Some(route) {
+                self.requested_route = Some(route);
+                // the loading screen takes the place of an earlier failure
+                crate::error::hide();
+                self.load(route);
+            }
+        }
+
+        let loaded_page = self.loaded_page.borrow_mut().take();
+        if let Some((route, page)) = loaded_page {
+            // a slow load may finish after the user already navigated elsewhere
+            if self.requested_route == Some(route) {
+                self.page = match page {
+                    Ok(mut page) => {
+                        page.resize(&self.graphics_context);
+                        Some(page)
+                    }
+                    Err(e) => {
+                        crate::error::show(&e);
+                        None
+                    }
+                };
+                self.page_route = Some(route);
+            }
+        }
+    }
+
+    fn load(&self, route: Route) {
+        // the loading screen shows the progress of this page's downloads only
+        crate::progress::reset();
+        let graphics_context = self.graphics_context.clone();
+        let loaded_page = self.loaded_page.clone();
+        let task = async move {
+            let page = route.load(&graphics_context).await;
+            *loaded_page.borrow_mut() = Some((route, page));
+        };
+        #[cfg(target_arch = "wasm32")]
+        wasm_bindgen_futures::spawn_local(task);
+        // there is no executor on the desktop, asset reads are blocking anyway
+        #[cfg(not(target_arch = "wasm32"))]
+        pollster::block_on(task);
+    }
+}
\ No newline at end of file
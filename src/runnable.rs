@@ -1,44 +1,116 @@
-use winit::dpi::PhysicalSize;
-use winit::event::{Event, WindowEvent};
-use winit::event_loop::EventLoop;
+use std::rc::Rc;
+use winit::event::{DeviceEvent, Event, WindowEvent};
+use winit::event_loop::{ControlFlow, EventLoop};
 use winit::window::Window;
+use crate::grapics_context::GraphicsContext;
+use crate::loading_screen::LoadingScreen;
+use crate::router::Router;
+use crate::timing::{FixedTimestep, FramePacer, TimingConfig};
 
 
-pub trait Runnable<'a> {
-    async fn new(window: &'a Window) -> Self;
+// pages only ever run on the single threaded event loop, so the futures don't need to be Send
+#[allow(async_fn_in_trait)]
+pub trait Runnable {
+    async fn new(graphics_context: &GraphicsContext<'_>) -> Result<Self, crate::error::Error> where Self: Sized;
 
-    fn update(&mut self, dt: instant::Duration);
+    // returns true if the event was consumed by the page
+    fn input(&mut self, graphics_context: &GraphicsContext, event: &WindowEvent) -> bool;
 
-    fn render(&mut self) -> Result<(), wgpu::SurfaceError>;
+    fn device_input(&mut self, graphics_context: &GraphicsContext, event: &DeviceEvent) -> bool;
 
-    fn resize(&mut self, new_size: PhysicalSize<u32>);
+    // called at a fixed rate, dt is always the same
+    fn update(&mut self, graphics_context: &GraphicsContext, dt: instant::Duration);
 
-    fn get_size(&self) -> PhysicalSize<u32>;
+    // alpha is how far the frame is between the last update and the next, for interpolating movement
+    fn render(&mut self, graphics_context: &GraphicsContext, alpha: f32) -> Result<(), wgpu::SurfaceError>;
 
-    fn window(&self) -> &Window;
+    // lets the runner stop redrawing in on-demand mode until the next input
+    fn is_animating(&self) -> bool {
+        true
+    }
+
+    // called after the graphics context has been resized
+    fn resize(&mut self, graphics_context: &GraphicsContext);
 }
 
-pub async fn run<'a, R: Runnable<'a>>(event_loop: EventLoop<()>, window: &'a Window) {
-    let mut app = R::new(window).await;
+pub async fn run(event_loop: EventLoop<()>, window: &'static Window, timing: TimingConfig) -> Result<(), crate::error::Error> {
+    let graphics_context = Rc::new(GraphicsContext::new(window).await?);
+    let mut router = Router::new(graphics_context.clone());
+    let mut loading_screen = LoadingScreen::new(&graphics_context);
+    let mut was_loading = false;
+
+    let mut timestep = FixedTimestep::new(timing.update_rate);
+    let mut frame_pacer = FramePacer::new(timing.max_frame_rate);
+    // set by anything that changes what's on screen, cleared once a frame shows it
+    let mut needs_redraw = true;
+    let mut visible = true;
 
-    let mut last_update_time = instant::Instant::now();
+    let mut last_frame_time = instant::Instant::now();
     event_loop.run(move |event, control_flow| {
         match event {
+            Event::DeviceEvent {
+                ref event,
+                ..
+            } => {
+                needs_redraw |= router.page().is_some_and(|page| page.device_input(&graphics_context, event));
+            }
             Event::WindowEvent {
                 ref event,
                 window_id
-            } if window_id == app.window().id() => {
+            } if window_id == window.id() => {
+                if router.page().is_some_and(|page| page.input(&graphics_context, event)) {
+                    needs_redraw = true;
+                    return;
+                }
                 match event {
                     WindowEvent::CloseRequested => control_flow.exit(),
-                    WindowEvent::Resized(physical_size) => app.resize(*physical_size),
+                    WindowEvent::Resized(physical_size) => {
+                        graphics_context.resize(*physical_size);
+                        if let Some(page) = router.page() {
+                            page.resize(&graphics_context);
+                        }
+                        needs_redraw = true;
+                    }
+                    // e.g. a hidden browser tab or the canvas scrolled out of view
+                    WindowEvent::Occluded(occluded) => {
+                        visible = !occluded;
+                        needs_redraw |= visible;
+                    }
                     WindowEvent::RedrawRequested => {
                         let now = instant::Instant::now();
-                        let dt = now - last_update_time;
-                        last_update_time = now;
-                        app.update(dt);
-                        match app.render(){
+                        let dt = now - last_frame_time;
+                        last_frame_time = now;
+                        frame_pacer.frame_started(now);
+                        needs_redraw = false;
+
+                        // the loading screen replaces the page until the next one is ready
+                        let loading = router.is_loading();
+                        if loading && !was_loading {
+                            loading_screen.reset();
+                        }
+                        was_loading = loading;
+                        let page = router.page().filter(|_| !loading);
+                        let result = match page {
+                            Some(page) => {
+                                for _ in 0..timestep.advance(now) {
+                                    page.update(&graphics_context, timestep.step());
+                                }
+                                page.render(&graphics_context, timestep.alpha())
+                            }
+                            None => {
+                                timestep.reset();
+                                loading_screen.update(&graphics_context, dt, crate::progress::current());
+                                loading_screen.render(&graphics_context)
+                            }
+                        };
+                        match result {
                             Ok(_) => {},
-                            Err(wgpu::SurfaceError::Lost) => app.resize(app.get_size()),
+                            Err(wgpu::SurfaceError::Lost) => {
+                                graphics_context.resize(graphics_context.size());
+                                if let Some(page) = router.page() {
+                                    page.resize(&graphics_context);
+                                }
+                            }
                             Err(wgpu::SurfaceError::OutOfMemory) => control_flow.exit(),
                             Err(e) => log::error!("{:?}", e)
                         }
@@ -46,8 +118,31 @@ pub async fn run<'a, R: Runnable<'a>>(event_loop: EventLoop<()>, window: &'a Win
                     _ => {}
                 }
             }
-            Event::AboutToWait => app.window().request_redraw(),
+            Event::AboutToWait => {
+                router.poll();
+
+                // a page finishing its load has to replace the loading screen even if it never animates
+                let loading = router.is_loading();
+                needs_redraw |= loading != was_loading;
+                let animating = loading || router.page().is_some_and(|page| page.is_animating());
+                if timing.on_demand && (!visible || !(needs_redraw || animating)) {
+                    // sleep until the next event, the idle time shouldn't be simulated afterwards
+                    timestep.reset();
+                    control_flow.set_control_flow(ControlFlow::Wait);
+                    return;
+                }
+
+                let wait = frame_pacer.time_until_next_frame(instant::Instant::now());
+                if wait.is_zero() {
+                    window.request_redraw();
+                    control_flow.set_control_flow(ControlFlow::Wait);
+                } else {
+                    control_flow.set_control_flow(ControlFlow::wait_duration(wait));
+                }
+            }
             _ => {}
         }
     }).unwrap();
+
+    Ok(())
 }
\ No newline at end of file
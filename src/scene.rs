@@ -0,0 +1,323 @@
+use std::ops::Range;
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, Point3, SquareMatrix, Transform};
+use wgpu::util::DeviceExt;
+use crate::light::Light;
+use std::rc::Rc;
+use crate::frustum::{CullStats, Frustum};
+use crate::model::{Instance, InstanceRaw, Mesh, Model};
+use crate::picking::{PickHit, Ray};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Attachment {
+    // indices into the scene's models and lights
+    Model(usize),
+    Light(usize),
+    Camera,
+}
+
+pub struct Node {
+    pub name: String,
+    pub transform: Instance,
+    pub attachment: Option<Attachment>,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    world: Matrix4<f32>,
+}
+
+impl Node {
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+
+    pub fn children(&self) -> &[NodeId] {
+        &self.children
+    }
+
+    // world matrix as of the last Scene::update
+    pub fn world_matrix(&self) -> Matrix4<f32> {
+        self.world
+    }
+}
+
+struct InstanceBatch {
+    buffer: wgpu::Buffer,
+    capacity: usize,
+    count: u32,
+    // kept on the CPU for culling
+    matrices: Vec<Matrix4<f32>>,
+}
+
+// one mesh of a model and the runs of its instances that passed culling
+pub struct MeshDraw<'a> {
+    // index into Scene::models
+    pub model_index: usize,
+    pub model: &'a Model,
+    pub mesh: &'a Mesh,
+    pub instance_buffer: &'a wgpu::Buffer,
+    pub instances: Vec<Range<u32>>,
+}
+
+pub struct Scene {
+    models: Vec<Rc<Model>>,
+    // as added, in the space of the node they're attached to
+    local_lights: Vec<Light>,
+    // children are always added after their parent, so a single pass in order resolves world matrices
+    nodes: Vec<Node>,
+    batches: Vec<InstanceBatch>,
+    // the local lights moved into world space by the last update
+    lights: Vec<Light>,
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        Self {
+            models: Vec::new(),
+            local_lights: Vec::new(),
+            nodes: Vec::new(),
+            batches: Vec::new(),
+            lights: Vec::new(),
+        }
+    }
+
+    // takes either a model or a handle to one shared through the asset manager
+    pub fn add_model(&mut self, device: &wgpu::Device, model: impl Into<Rc<Model>>) -> usize {
+        self.models.push(model.into());
+        self.batches.push(Self::create_batch(device, 1));
+        self.models.len() - 1
+    }
+
+    // lights are defined in the space of the node they get attached to
+    pub fn add_light(&mut self, light: Light) -> usize {
+        self.local_lights.push(light);
+        self.local_lights.len() - 1
+    }
+
+    pub fn add_node(&mut self, name: &str, parent: Option<NodeId>, transform: Instance, attachment: Option<Attachment>) -> NodeId {
+        let id = NodeId(self.nodes.len());
+        let parent_world = match parent {
+            Some(parent) => {
+                self.nodes[parent.0].children.push(id);
+                self.nodes[parent.0].world
+            }
+            None => Matrix4::identity(),
+        };
+        self.nodes.push(Node {
+            name: name.to_string(),
+            world: parent_world * transform.to_matrix(),
+            transform,
+            attachment,
+            parent,
+            children: Vec::new(),
+        });
+        id
+    }
+
+    // adds the output of resources::load_gltf, one node per instance under a common parent
+    pub fn add_gltf(&mut self, device: &wgpu::Device, name: &str, parent: Option<NodeId>, gltf: Vec<(impl Into<Rc<Model>>, Vec<Instance>)>) -> NodeId {
+        let root = self.add_node(name, parent, Instance::identity(), None);
+        for (model, instances) in gltf {
+            let model: Rc<Model> = model.into();
+            let model_name = model.meshes.first().map(|mesh| mesh.name.clone()).unwrap_or_default();
+            let model = self.add_model(device, model);
+            for instance in instances {
+                self.add_node(&model_name, Some(root), instance, Some(Attachment::Model(model)));
+            }
+        }
+        root
+    }
+
+    pub fn node(&self, id: NodeId) -> &Node {
+        &self.nodes[id.0]
+    }
+
+    pub fn node_mut(&mut self, id: NodeId) -> &mut Node {
+        &mut self.nodes[id.0]
+    }
+
+    pub fn find_node(&self, name: &str) -> Option<NodeId> {
+        self.nodes.iter().position(|node| node.name == name).map(NodeId)
+    }
+
+    pub fn models(&self) -> &[Rc<Model>] {
+        &self.models
+    }
+
+    pub fn light_mut(&mut self, light: usize) -> &mut Light {
+        &mut self.local_lights[light]
+    }
+
+    // in world space as of the last update
+    pub fn lights(&self) -> &[Light] {
+        &self.lights
+    }
+
+    // world matrix of the first node with a camera attached
+    pub fn camera_matrix(&self) -> Option<Matrix4<f32>> {
+        self.nodes.iter()
+            .find(|node| node.attachment == Some(Attachment::Camera))
+            .map(|node| node.world)
+    }
+
+    // recomputes world matrices and uploads the instances of every model
+    pub fn update(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        for i in 0..self.nodes.len() {
+            let parent_world = self.nodes[i].parent
+                .map(|parent| self.nodes[parent.0].world)
+                .unwrap_or(Matrix4::identity());
+            self.nodes[i].world = parent_world * self.nodes[i].transform.to_matrix();
+        }
+
+        let mut instances = vec![Vec::new(); self.models.len()];
+        self.lights.clear();
+        self.lights.extend_from_slice(&self.local_lights);
+        for node in &self.nodes {
+            match node.attachment {
+                Some(Attachment::Model(model)) => instances[model].push(InstanceRaw::from_matrix(node.world)),
+                Some(Attachment::Light(light)) => {
+                    let world_light = &mut self.lights[light];
+                    world_light.position = node.world.transform_point(self.local_lights[light].position);
+                    world_light.direction = node.world.transform_vector(self.local_lights[light].direction).normalize();
+                }
+                _ => {}
+            }
+        }
+
+        for (batch, instances) in self.batches.iter_mut().zip(instances) {
+            if instances.len() > batch.capacity {
+                *batch = Self::create_batch(device, instances.len().next_power_of_two());
+            }
+            queue.write_buffer(&batch.buffer, 0, bytemuck::cast_slice(&instances));
+            batch.count = instances.len() as u32;
+            batch.matrices.clear();
+        }
+        for node in &self.nodes {
+            if let Some(Attachment::Model(model)) = node.attachment {
+                self.batches[model].matrices.push(node.world);
+            }
+        }
+    }
+
+    // every model with its index, instance buffer and the range of instances to draw
+    pub fn batches(&self) -> Vec<(usize, &Model, &wgpu::Buffer, Range<u32>)> {
+        self.models.iter()
+            .zip(&self.batches)
+            .enumerate()
+            .filter(|(_, (_, batch))| batch.count > 0)
+            .map(|(index, (model, batch))| (index, model.as_ref(), &batch.buffer, 0..batch.count))
+            .collect()
+    }
+
+    // the meshes with at least one instance inside the frustum, testing the cheaper sphere before the box
+    pub fn cull(&self, frustum: &Frustum, stats: &mut CullStats) -> Vec<MeshDraw<'_>> {
+        *stats = CullStats::default();
+        let mut draws = Vec::new();
+        for (model_index, (model, batch)) in self.models.iter().zip(&self.batches).enumerate() {
+            // skinned meshes are posed on the gpu, their bind-pose bounds say nothing about where they end up
+            let skinned = model.skeleton.is_some();
+            for mesh in &model.meshes {
+                let visible: Vec<bool> = batch.matrices.iter()
+                    .map(|matrix| skinned || (frustum.intersects_sphere(&mesh.bounding_sphere.transform(matrix))
+                        && frustum.intersects_aabb(&mesh.aabb.transform(matrix))))
+                    .collect();
+                let drawn = visible.iter().filter(|&&visible| visible).count() as u32;
+                stats.drawn += drawn;
+                stats.culled += visible.len() as u32 - drawn;
+                let instances = instance_ranges(&visible);
+                if !instances.is_empty() {
+                    draws.push(MeshDraw {
+                        model_index,
+                        model: model.as_ref(),
+                        mesh,
+                        instance_buffer: &batch.buffer,
+                        instances,
+                    });
+                }
+            }
+        }
+        draws
+    }
+
+    // the closest triangle hit by a world space ray, only instances whose box the ray crosses are tested.
+    // skinned meshes are picked in their bind pose, the joint matrices never reach the scene
+    pub fn pick(&self, ray: &Ray) -> Option<PickHit> {
+        let mut closest: Option<PickHit> = None;
+        for (model_index, (model, batch)) in self.models.iter().zip(&self.batches).enumerate() {
+            for (instance, matrix) in batch.matrices.iter().enumerate() {
+                let Some(inverse) = matrix.invert() else { continue };
+                let local_ray = ray.transform(&inverse);
+                for (mesh_index, mesh) in model.meshes.iter().enumerate() {
+                    let max_distance = closest.map_or(f32::INFINITY, |hit| hit.distance);
+                    if local_ray.intersect_aabb(&mesh.aabb).is_none_or(|distance| distance >= max_distance) {
+                        continue;
+                    }
+                    for triangle in mesh.indices.chunks_exact(3) {
+                        let [a, b, c] = [0, 1, 2].map(|i| mesh.positions[triangle[i] as usize]);
+                        let Some(distance) = local_ray.intersect_triangle(a, b, c) else { continue };
+                        if closest.is_none_or(|hit| distance < hit.distance) {
+                            closest = Some(PickHit {
+                                model: model_index,
+                                mesh: mesh_index,
+                                instance,
+                                distance,
+                                point: ray.at(distance),
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        closest
+    }
+
+    pub fn world_position(&self, id: NodeId) -> Point3<f32> {
+        Point3::from_vec(self.nodes[id.0].world.w.truncate())
+    }
+
+    fn create_batch(device: &wgpu::Device, capacity: usize) -> InstanceBatch {
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Instance Buffer"),
+            contents: bytemuck::cast_slice(&vec![InstanceRaw::from_matrix(Matrix4::identity()); capacity]),
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        });
+        InstanceBatch {
+            buffer,
+            capacity,
+            count: 0,
+            matrices: Vec::new(),
+        }
+    }
+}
+
+// the indices of visible instances, neighbouring ones merged so they're drawn in one call
+fn instance_ranges(visible: &[bool]) -> Vec<Range<u32>> {
+    let mut ranges: Vec<Range<u32>> = Vec::new();
+    for (i, _) in visible.iter().enumerate().filter(|(_, &visible)| visible) {
+        let i = i as u32;
+        match ranges.last_mut() {
+            Some(range) if range.end == i => range.end = i + 1,
+            _ => ranges.push(i..i + 1),
+        }
+    }
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adjacent_visible_instances_share_a_range() {
+        assert_eq!(instance_ranges(&[true, true, false, true, true, true, false]), vec![0..2, 3..6]);
+        assert_eq!(instance_ranges(&[false, true]), vec![1..2]);
+        assert!(instance_ranges(&[false, false]).is_empty());
+    }
+}
\ No newline at end of file
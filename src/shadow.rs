@@ -0,0 +1,182 @@
+use std::ops::Range;
+use cgmath::Point3;
+use wgpu::util::DeviceExt;
+use crate::light::Light;
+use crate::model::{Model, Vertex};
+use crate::morph::MorphTargets;
+use crate::texture::Texture;
+
+pub const MAX_SHADOW_MAPS: u32 = 4;
+
+#[derive(Debug, Copy, Clone)]
+pub struct ShadowConfig {
+    pub resolution: u32,
+    // subtracted from the fragment depth before comparing
+    pub bias: f32,
+    // world space offset along the surface normal before projecting into the shadow map
+    pub normal_bias: f32,
+    // rasterizer bias applied while rendering the shadow map
+    pub constant_bias: i32,
+    pub slope_bias: f32,
+    // directional shadows cover a box of this half size around the center
+    pub directional_extent: f32,
+    pub directional_center: Point3<f32>,
+}
+
+impl Default for ShadowConfig {
+    fn default() -> Self {
+        Self {
+            resolution: 1024,
+            bias: 0.001,
+            normal_bias: 0.02,
+            constant_bias: 2,
+            slope_bias: 2.0,
+            directional_extent: 5.0,
+            directional_center: Point3::new(0.0, 0.0, 0.0),
+        }
+    }
+}
+
+pub struct ShadowMaps {
+    pub config: ShadowConfig,
+    pub texture: Texture,
+    layer_views: Vec<wgpu::TextureView>,
+    light_buffers: Vec<wgpu::Buffer>,
+    light_bind_groups: Vec<wgpu::BindGroup>,
+    // bound for meshes without morph targets
+    no_morph: MorphTargets,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl ShadowMaps {
+    // camera_bind_group_layout is only there for the joint matrices of skinned models
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, config: ShadowConfig, camera_bind_group_layout: &wgpu::BindGroupLayout) -> Self {
+        let texture = Texture::create_shadow_map_array(device, config.resolution, MAX_SHADOW_MAPS, "shadow_maps");
+        let layer_views = (0..MAX_SHADOW_MAPS)
+            .map(|layer| texture.texture.create_view(&wgpu::TextureViewDescriptor {
+                label: Some("shadow map layer"),
+                dimension: Some(wgpu::TextureViewDimension::D2),
+                base_array_layer: layer,
+                array_layer_count: Some(1),
+                ..Default::default()
+            }))
+            .collect::<Vec<_>>();
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }
+            ],
+            label: Some("shadow bind group layout"),
+        });
+
+        let light_buffers = (0..MAX_SHADOW_MAPS)
+            .map(|_| device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Shadow Light Buffer"),
+                contents: bytemuck::cast_slice(&[[[0.0f32; 4]; 4]]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            }))
+            .collect::<Vec<_>>();
+        let light_bind_groups = light_buffers.iter()
+            .map(|buffer| device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: buffer.as_entire_binding(),
+                    }
+                ],
+                label: Some("shadow bind group"),
+            }))
+            .collect::<Vec<_>>();
+
+        let morph_bind_group_layout = MorphTargets::create_bind_group_layout(device);
+        let no_morph = MorphTargets::empty(device, queue, &morph_bind_group_layout);
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Shadow pipeline layout"),
+            bind_group_layouts: &[&bind_group_layout, camera_bind_group_layout, &morph_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Shadow Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/shadow.wgsl").into()),
+        });
+        // depth only, no fragment stage
+        let pipeline = crate::wgpu_helpers::RenderPipelineBuilder::new(&shader)
+            .label("Shadow Pipeline")
+            .layout(&pipeline_layout)
+            .fragment_entry_point(None)
+            .vertex_layouts(&[crate::model::ModelVertex::desc(), crate::model::InstanceRaw::desc()])
+            .depth(Texture::DEPTH_FORMAT, true, wgpu::CompareFunction::LessEqual)
+            .depth_bias(config.constant_bias, config.slope_bias)
+            .build(device);
+
+        Self {
+            config,
+            texture,
+            layer_views,
+            light_buffers,
+            light_bind_groups,
+            no_morph,
+            pipeline,
+        }
+    }
+
+    // renders every model into the shadow map of each shadow casting light,
+    // each with the camera bind group holding its joints
+    pub fn render(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        queue: &wgpu::Queue,
+        lights: &[Light],
+        models: &[(&Model, &wgpu::Buffer, Range<u32>, &wgpu::BindGroup)],
+    ) {
+        let layers = crate::light::shadow_layers(lights, &self.config);
+        for (light, layer) in lights.iter().zip(layers) {
+            let (Some(layer), Some(view_proj)) = (layer, light.shadow_view_proj(&self.config)) else {
+                continue;
+            };
+            let layer = layer as usize;
+            let view_proj: [[f32; 4]; 4] = view_proj.into();
+            queue.write_buffer(&self.light_buffers[layer], 0, bytemuck::cast_slice(&[view_proj]));
+
+            let mut shadow_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Shadow pass"),
+                color_attachments: &[],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.layer_views[layer],
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+
+            shadow_pass.set_pipeline(&self.pipeline);
+            shadow_pass.set_bind_group(0, &self.light_bind_groups[layer], &[]);
+            for (model, instance_buffer, instances, camera_bind_group) in models {
+                shadow_pass.set_bind_group(1, camera_bind_group, &[]);
+                shadow_pass.set_vertex_buffer(1, instance_buffer.slice(..));
+                for mesh in &model.meshes {
+                    let morph_targets = mesh.morph_targets.as_ref().unwrap_or(&self.no_morph);
+                    shadow_pass.set_bind_group(2, &morph_targets.bind_group, &[]);
+                    shadow_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+                    shadow_pass.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                    shadow_pass.draw_indexed(0..mesh.num_elements, 0, instances.clone());
+                }
+            }
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,94 @@
+use bytemuck::{Pod, Zeroable};
+use cgmath::{Matrix4, SquareMatrix, Vector4};
+use wgpu::util::DeviceExt;
+use crate::camera::{CameraView, Projection};
+use crate::environment::Environment;
+use crate::grapics_context::GraphicsContext;
+use crate::texture::Texture;
+use crate::wgpu_helpers::{BlendMode, RenderPipelineBuilder};
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+struct SkyboxUniform {
+    inverse_view_proj: [[f32; 4]; 4],
+}
+
+// Draws an environment's sky behind everything else, in the same pass as the scene after it
+pub struct Skybox {
+    shader: wgpu::ShaderModule,
+    pipeline: wgpu::RenderPipeline,
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+impl Skybox {
+    pub fn new(graphics_context: &GraphicsContext, environment: &Environment, format: wgpu::TextureFormat, sample_count: u32) -> Self {
+        let device = &graphics_context.device;
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Skybox Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/skybox.wgsl").into()),
+        });
+        let pipeline = Self::create_pipeline(device, &shader, format, sample_count);
+
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Skybox Buffer"),
+            contents: bytemuck::cast_slice(&[SkyboxUniform { inverse_view_proj: Matrix4::identity().into() }]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&environment.skybox.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&environment.skybox.sampler),
+                },
+            ],
+            label: Some("skybox bind group"),
+        });
+
+        Self {
+            shader,
+            pipeline,
+            uniform_buffer,
+            bind_group,
+        }
+    }
+
+    // tested against the scene's depth without writing to it
+    fn create_pipeline(device: &wgpu::Device, shader: &wgpu::ShaderModule, format: wgpu::TextureFormat, sample_count: u32) -> wgpu::RenderPipeline {
+        RenderPipelineBuilder::new(shader)
+            .label("Skybox Pipeline")
+            .color_target(format, BlendMode::Replace)
+            .depth(Texture::DEPTH_FORMAT, false, wgpu::CompareFunction::LessEqual)
+            .cull_mode(None)
+            .sample_count(sample_count)
+            .build(device)
+    }
+
+    // has to match the pass it's drawn in, e.g. after toggling MSAA
+    pub fn set_sample_count(&mut self, graphics_context: &GraphicsContext, format: wgpu::TextureFormat, sample_count: u32) {
+        self.pipeline = Self::create_pipeline(&graphics_context.device, &self.shader, format, sample_count);
+    }
+
+    pub fn update<C: CameraView>(&self, queue: &wgpu::Queue, camera: &C, projection: &Projection) {
+        // the sky is infinitely far away, moving the camera doesn't change it
+        let mut view = camera.calc_matrix();
+        view.w = Vector4::new(0.0, 0.0, 0.0, 1.0);
+        let inverse_view_proj = (projection.calc_matrix() * view).invert().unwrap_or(Matrix4::identity());
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[SkyboxUniform { inverse_view_proj: inverse_view_proj.into() }]));
+    }
+
+    pub fn render<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
\ No newline at end of file
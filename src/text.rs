@@ -0,0 +1,587 @@
+use std::collections::HashMap;
+use ab_glyph::{Font as _, FontVec, GlyphId, PxScale, ScaleFont};
+use bytemuck::{Pod, Zeroable};
+use cgmath::{Matrix4, Point3, Transform};
+use wgpu::util::DeviceExt;
+use crate::grapics_context::GraphicsContext;
+use crate::texture::Texture;
+use crate::wgpu_helpers::{BlendMode, RenderPipelineBuilder};
+
+// glyphs are rasterized once at this size and scaled by the distance field afterwards
+const BAKE_SIZE: f32 = 48.0;
+// how far from the outline in atlas pixels the distance field reaches
+const SPREAD: usize = 6;
+const ATLAS_WIDTH: usize = 512;
+// printable ASCII plus Polish letters
+pub const DEFAULT_CHARSET: &str = " !\"#$%&'()*+,-./0123456789:;<=>?@ABCDEFGHIJKLMNOPQRSTUVWXYZ[\\]^_`abcdefghijklmnopqrstuvwxyz{|}~ąćęłńóśźżĄĆĘŁŃÓŚŹŻ";
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TextAlign {
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TextStyle {
+    // pixels on screen, world units in the scene
+    pub size: f32,
+    pub color: [f32; 4],
+    // lines are broken between words to fit, in the same units as size
+    pub max_width: Option<f32>,
+    pub align: TextAlign,
+    // multiple of the font's own line height
+    pub line_spacing: f32,
+}
+
+impl TextStyle {
+    pub fn new(size: f32, color: [f32; 4]) -> Self {
+        Self {
+            size,
+            color,
+            max_width: None,
+            align: TextAlign::Left,
+            line_spacing: 1.0,
+        }
+    }
+
+    pub fn with_max_width(mut self, max_width: f32) -> Self {
+        self.max_width = Some(max_width);
+        self
+    }
+
+    pub fn with_align(mut self, align: TextAlign) -> Self {
+        self.align = align;
+        self
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Glyph {
+    id: GlyphId,
+    uv_min: [f32; 2],
+    uv_max: [f32; 2],
+    // of the padded bitmap relative to the pen on the baseline, y down, in pixels at BAKE_SIZE
+    offset: [f32; 2],
+    size: [f32; 2],
+}
+
+// A positioned glyph, y goes down from the top left corner of the text
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct GlyphQuad {
+    pub min: [f32; 2],
+    pub max: [f32; 2],
+    pub uv_min: [f32; 2],
+    pub uv_max: [f32; 2],
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextLayout {
+    pub glyphs: Vec<GlyphQuad>,
+    pub width: f32,
+    pub height: f32,
+}
+
+// A font with a signed distance field atlas of its glyphs
+pub struct Font {
+    font: FontVec,
+    glyphs: HashMap<char, Glyph>,
+    atlas: Texture,
+}
+
+impl Font {
+    pub fn from_bytes(device: &wgpu::Device, queue: &wgpu::Queue, data: Vec<u8>, charset: &str, label: &str) -> anyhow::Result<Self> {
+        let font = FontVec::try_from_vec(data)
+            .map_err(|e| anyhow::anyhow!("{} is not a valid font: {}", label, e))?;
+        let scaled = font.as_scaled(PxScale::from(BAKE_SIZE));
+
+        // shelf packing, glyphs go left to right in rows as tall as the tallest glyph in them
+        let mut bitmaps = Vec::new();
+        let mut glyphs = HashMap::new();
+        let (mut x, mut y, mut row_height) = (0, 0, 0);
+        for c in charset.chars() {
+            let id = font.glyph_id(c);
+            let (bitmap, width, height, offset) = match scaled.outline_glyph(id.with_scale(BAKE_SIZE)) {
+                Some(outline) => {
+                    let bounds = outline.px_bounds();
+                    let (width, height) = (bounds.width() as usize + 2 * SPREAD, bounds.height() as usize + 2 * SPREAD);
+                    let mut coverage = vec![0.0; width * height];
+                    outline.draw(|gx, gy, c| coverage[(gy as usize + SPREAD) * width + gx as usize + SPREAD] = c);
+                    let offset = [bounds.min.x - SPREAD as f32, bounds.min.y - SPREAD as f32];
+                    (signed_distance_field(&coverage, width, height), width, height, offset)
+                }
+                // spaces and anything else without an outline only advance the pen
+                None => (Vec::new(), 0, 0, [0.0; 2]),
+            };
+            if x + width > ATLAS_WIDTH {
+                x = 0;
+                y += row_height;
+                row_height = 0;
+            }
+            glyphs.insert(c, Glyph {
+                id,
+                uv_min: [x as f32, y as f32],
+                uv_max: [(x + width) as f32, (y + height) as f32],
+                offset,
+                size: [width as f32, height as f32],
+            });
+            bitmaps.push((bitmap, x, y, width));
+            x += width;
+            row_height = row_height.max(height);
+        }
+        let atlas_height = (y + row_height).max(1).next_power_of_two();
+        let max_height = device.limits().max_texture_dimension_2d as usize;
+        if atlas_height > max_height {
+            anyhow::bail!("{} needs a {} texel high atlas for {} characters, the device supports {}", label, atlas_height, charset.chars().count(), max_height);
+        }
+
+        let mut pixels = vec![0u8; ATLAS_WIDTH * atlas_height];
+        for (bitmap, x, y, width) in bitmaps {
+            for (row, line) in bitmap.chunks_exact(width.max(1)).enumerate() {
+                let start = (y + row) * ATLAS_WIDTH + x;
+                pixels[start..start + width].copy_from_slice(line);
+            }
+        }
+        for glyph in glyphs.values_mut() {
+            glyph.uv_min = [glyph.uv_min[0] / ATLAS_WIDTH as f32, glyph.uv_min[1] / atlas_height as f32];
+            glyph.uv_max = [glyph.uv_max[0] / ATLAS_WIDTH as f32, glyph.uv_max[1] / atlas_height as f32];
+        }
+
+        let atlas = Self::create_atlas(device, queue, &pixels, atlas_height as u32, label);
+        Ok(Self {
+            font,
+            glyphs,
+            atlas,
+        })
+    }
+
+    fn create_atlas(device: &wgpu::Device, queue: &wgpu::Queue, pixels: &[u8], height: u32, label: &str) -> Texture {
+        let size = wgpu::Extent3d {
+            width: ATLAS_WIDTH as u32,
+            height,
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture_with_data(queue, &wgpu::TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        }, wgpu::util::TextureDataOrder::LayerMajor, pixels);
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        // the distance is interpolated between texels, that's what keeps edges sharp when scaled up
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+        Texture {
+            texture,
+            view,
+            sampler,
+        }
+    }
+
+    pub fn atlas(&self) -> &Texture {
+        &self.atlas
+    }
+
+    // characters missing from the atlas are drawn as '?' when it has one
+    fn glyph(&self, c: char) -> Option<&Glyph> {
+        self.glyphs.get(&c).or_else(|| self.glyphs.get(&'?'))
+    }
+
+    fn line_width(&self, line: &str, size: f32) -> f32 {
+        let scaled = self.font.as_scaled(PxScale::from(size));
+        let mut width = 0.0;
+        let mut previous = None;
+        for glyph in line.chars().filter_map(|c| self.glyph(c)) {
+            if let Some(previous) = previous {
+                width += scaled.kern(previous, glyph.id);
+            }
+            width += scaled.h_advance(glyph.id);
+            previous = Some(glyph.id);
+        }
+        width
+    }
+
+    // greedy, a word longer than max_width gets a line of its own
+    fn wrap<'a>(&self, text: &'a str, style: &TextStyle) -> Vec<&'a str> {
+        let mut lines = Vec::new();
+        for paragraph in text.lines() {
+            let Some(max_width) = style.max_width else {
+                lines.push(paragraph);
+                continue;
+            };
+            let mut start = 0;
+            let mut end = 0;
+            for (word_start, word) in paragraph.split(' ').scan(0, |position, word| {
+                let word_start = *position;
+                *position += word.len() + 1;
+                Some((word_start, word))
+            }) {
+                let word_end = word_start + word.len();
+                if end > start && self.line_width(&paragraph[start..word_end], style.size) > max_width {
+                    lines.push(&paragraph[start..end]);
+                    start = word_start;
+                }
+                end = word_end;
+            }
+            lines.push(&paragraph[start..end]);
+        }
+        lines
+    }
+
+    pub fn layout(&self, text: &str, style: &TextStyle) -> TextLayout {
+        let scaled = self.font.as_scaled(PxScale::from(style.size));
+        let scale = style.size / BAKE_SIZE;
+        let line_height = (scaled.ascent() - scaled.descent() + scaled.line_gap()) * style.line_spacing;
+
+        let lines = self.wrap(text, style);
+        let widths: Vec<f32> = lines.iter().map(|line| self.line_width(line, style.size)).collect();
+        let width = style.max_width.unwrap_or(widths.iter().copied().fold(0.0, f32::max));
+
+        let mut glyphs = Vec::new();
+        for (i, (line, line_width)) in lines.iter().zip(widths).enumerate() {
+            let mut x = match style.align {
+                TextAlign::Left => 0.0,
+                TextAlign::Center => (width - line_width) * 0.5,
+                TextAlign::Right => width - line_width,
+            };
+            let baseline = scaled.ascent() + i as f32 * line_height;
+            let mut previous = None;
+            for glyph in line.chars().filter_map(|c| self.glyph(c)) {
+                if let Some(previous) = previous {
+                    x += scaled.kern(previous, glyph.id);
+                }
+                if glyph.size[0] > 0.0 {
+                    let min = [x + glyph.offset[0] * scale, baseline + glyph.offset[1] * scale];
+                    glyphs.push(GlyphQuad {
+                        min,
+                        max: [min[0] + glyph.size[0] * scale, min[1] + glyph.size[1] * scale],
+                        uv_min: glyph.uv_min,
+                        uv_max: glyph.uv_max,
+                    });
+                }
+                x += scaled.h_advance(glyph.id);
+                previous = Some(glyph.id);
+            }
+        }
+
+        TextLayout {
+            glyphs,
+            width,
+            height: lines.len() as f32 * line_height,
+        }
+    }
+}
+
+// 0.5 on the outline, larger inside, reaching 0 and 1 SPREAD pixels away from it
+fn signed_distance_field(coverage: &[f32], width: usize, height: usize) -> Vec<u8> {
+    let inside: Vec<bool> = coverage.iter().map(|&c| c > 0.5).collect();
+    let to_inside = squared_distance_transform(&inside.iter().map(|&inside| !inside).collect::<Vec<_>>(), width, height);
+    let to_outside = squared_distance_transform(&inside, width, height);
+    to_inside.iter().zip(&to_outside)
+        .map(|(to_inside, to_outside)| {
+            let distance = to_outside.sqrt() - to_inside.sqrt();
+            ((0.5 + distance / (2.0 * SPREAD as f32)).clamp(0.0, 1.0) * 255.0).round() as u8
+        })
+        .collect()
+}
+
+// Felzenszwalb and Huttenlocher's exact transform, the squared distance from every pixel
+// to the nearest one that isn't set in far
+fn squared_distance_transform(far: &[bool], width: usize, height: usize) -> Vec<f32> {
+    // large but finite, infinities turn into NaNs in the parabola intersections
+    const FAR: f32 = 1e20;
+    let mut grid: Vec<f32> = far.iter().map(|&far| if far { FAR } else { 0.0 }).collect();
+    let mut f = vec![0.0; width.max(height)];
+    let mut d = vec![0.0; width.max(height)];
+    for x in 0..width {
+        for y in 0..height {
+            f[y] = grid[y * width + x];
+        }
+        distance_transform_1d(&f[..height], &mut d[..height]);
+        for y in 0..height {
+            grid[y * width + x] = d[y];
+        }
+    }
+    for y in 0..height {
+        distance_transform_1d(&grid[y * width..(y + 1) * width], &mut d[..width]);
+        grid[y * width..(y + 1) * width].copy_from_slice(&d[..width]);
+    }
+    grid
+}
+
+// lower envelope of the parabolas rooted at every sample
+fn distance_transform_1d(f: &[f32], d: &mut [f32]) {
+    let n = f.len();
+    let mut v = vec![0usize; n];
+    let mut z = vec![0.0f32; n + 1];
+    let mut k = 0;
+    z[0] = f32::NEG_INFINITY;
+    z[1] = f32::INFINITY;
+    let intersect = |q: usize, p: usize| ((f[q] + (q * q) as f32) - (f[p] + (p * p) as f32)) / (2.0 * q as f32 - 2.0 * p as f32);
+    for q in 1..n {
+        let mut s = intersect(q, v[k]);
+        while s <= z[k] {
+            k -= 1;
+            s = intersect(q, v[k]);
+        }
+        k += 1;
+        v[k] = q;
+        z[k] = s;
+        z[k + 1] = f32::INFINITY;
+    }
+    k = 0;
+    for (q, d) in d.iter_mut().enumerate() {
+        while z[k + 1] < q as f32 {
+            k += 1;
+        }
+        let offset = q as f32 - v[k] as f32;
+        *d = offset * offset + f[v[k]];
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+struct TextVertex {
+    position: [f32; 3],
+    tex_coords: [f32; 2],
+    color: [f32; 4],
+}
+
+impl TextVertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 3] = wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x2, 2 => Float32x4];
+
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<TextVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+// grows to fit whatever got queued since the last frame
+struct TextBuffer {
+    buffer: wgpu::Buffer,
+    capacity: usize,
+    count: u32,
+}
+
+impl TextBuffer {
+    fn new(device: &wgpu::Device, capacity: usize, label: &str) -> Self {
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: (capacity * std::mem::size_of::<TextVertex>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        Self {
+            buffer,
+            capacity,
+            count: 0,
+        }
+    }
+
+    fn upload(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, vertices: &[TextVertex], label: &str) {
+        if vertices.len() > self.capacity {
+            *self = Self::new(device, vertices.len().next_power_of_two(), label);
+        }
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(vertices));
+        self.count = vertices.len() as u32;
+    }
+}
+
+// Immediate mode text, queue strings every frame then prepare and render them.
+// Screen text is drawn on top of the finished frame, world text in the scene's pass with its camera.
+pub struct TextRenderer {
+    font: Font,
+    shader: wgpu::ShaderModule,
+    world_layout: wgpu::PipelineLayout,
+    screen_pipeline: wgpu::RenderPipeline,
+    world_pipeline: wgpu::RenderPipeline,
+    atlas_bind_group: wgpu::BindGroup,
+    screen_vertices: Vec<TextVertex>,
+    world_vertices: Vec<TextVertex>,
+    screen_buffer: TextBuffer,
+    world_buffer: TextBuffer,
+}
+
+impl TextRenderer {
+    // world_format and sample_count have to match the pass world text is drawn in
+    pub fn new(graphics_context: &GraphicsContext, font: Font, camera_bind_group_layout: &wgpu::BindGroupLayout, world_format: wgpu::TextureFormat, sample_count: u32) -> Self {
+        let device = &graphics_context.device;
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Text Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/text.wgsl").into()),
+        });
+
+        let atlas_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("text atlas bind group layout"),
+        });
+        let atlas_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &atlas_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&font.atlas.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&font.atlas.sampler),
+                },
+            ],
+            label: Some("text atlas bind group"),
+        });
+
+        // screen text doesn't need the camera, so its layout stops at the atlas
+        let screen_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Screen text pipeline layout"),
+            bind_group_layouts: &[&atlas_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let world_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("World text pipeline layout"),
+            bind_group_layouts: &[&atlas_bind_group_layout, camera_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let screen_pipeline = RenderPipelineBuilder::new(&shader)
+            .label("Screen Text Pipeline")
+            .layout(&screen_layout)
+            .vertex_entry_point("vs_screen")
+            .vertex_layouts(&[TextVertex::desc()])
+            .color_target(graphics_context.format(), BlendMode::Alpha)
+            .cull_mode(None)
+            .build(device);
+        let world_pipeline = Self::create_world_pipeline(device, &world_layout, &shader, world_format, sample_count);
+
+        Self {
+            font,
+            shader,
+            world_layout,
+            screen_pipeline,
+            world_pipeline,
+            atlas_bind_group,
+            screen_vertices: Vec::new(),
+            world_vertices: Vec::new(),
+            screen_buffer: TextBuffer::new(device, 1024, "Screen Text Buffer"),
+            world_buffer: TextBuffer::new(device, 1024, "World Text Buffer"),
+        }
+    }
+
+    // occluded by the scene but doesn't occlude it, and readable from behind
+    fn create_world_pipeline(device: &wgpu::Device, layout: &wgpu::PipelineLayout, shader: &wgpu::ShaderModule, format: wgpu::TextureFormat, sample_count: u32) -> wgpu::RenderPipeline {
+        RenderPipelineBuilder::new(shader)
+            .label("World Text Pipeline")
+            .layout(layout)
+            .vertex_entry_point("vs_world")
+            .vertex_layouts(&[TextVertex::desc()])
+            .color_target(format, BlendMode::Alpha)
+            .depth(Texture::DEPTH_FORMAT, false, wgpu::CompareFunction::LessEqual)
+            .cull_mode(None)
+            .sample_count(sample_count)
+            .build(device)
+    }
+
+    pub fn set_sample_count(&mut self, graphics_context: &GraphicsContext, format: wgpu::TextureFormat, sample_count: u32) {
+        self.world_pipeline = Self::create_world_pipeline(&graphics_context.device, &self.world_layout, &self.shader, format, sample_count);
+    }
+
+    pub fn font(&self) -> &Font {
+        &self.font
+    }
+
+    // position of the top left corner in window pixels
+    pub fn queue_screen(&mut self, text: &str, position: [f32; 2], style: &TextStyle) {
+        let layout = self.font.layout(text, style);
+        push_quads(&mut self.screen_vertices, &layout, style.color, |[x, y]| Point3::new(position[0] + x, position[1] + y, 0.0));
+    }
+
+    // the text's top left corner sits at the transform's origin, reading along +x with +y up
+    pub fn queue_world(&mut self, text: &str, transform: Matrix4<f32>, style: &TextStyle) {
+        let layout = self.font.layout(text, style);
+        push_quads(&mut self.world_vertices, &layout, style.color, |[x, y]| transform.transform_point(Point3::new(x, -y, 0.0)));
+    }
+
+    // uploads and clears everything queued since the last call
+    pub fn prepare(&mut self, graphics_context: &GraphicsContext) {
+        // pixels to clip space, done here since the window might have been resized after queueing
+        let size = graphics_context.size();
+        for vertex in &mut self.screen_vertices {
+            vertex.position[0] = vertex.position[0] / size.width.max(1) as f32 * 2.0 - 1.0;
+            vertex.position[1] = 1.0 - vertex.position[1] / size.height.max(1) as f32 * 2.0;
+        }
+        self.screen_buffer.upload(&graphics_context.device, &graphics_context.queue, &self.screen_vertices, "Screen Text Buffer");
+        self.world_buffer.upload(&graphics_context.device, &graphics_context.queue, &self.world_vertices, "World Text Buffer");
+        self.screen_vertices.clear();
+        self.world_vertices.clear();
+    }
+
+    pub fn has_screen_text(&self) -> bool {
+        self.screen_buffer.count > 0
+    }
+
+    pub fn render_screen<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        if self.screen_buffer.count == 0 {
+            return;
+        }
+        render_pass.set_pipeline(&self.screen_pipeline);
+        render_pass.set_bind_group(0, &self.atlas_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.screen_buffer.buffer.slice(..));
+        render_pass.draw(0..self.screen_buffer.count, 0..1);
+    }
+
+    pub fn render_world<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, camera_bind_group: &'a wgpu::BindGroup) {
+        if self.world_buffer.count == 0 {
+            return;
+        }
+        render_pass.set_pipeline(&self.world_pipeline);
+        render_pass.set_bind_group(0, &self.atlas_bind_group, &[]);
+        render_pass.set_bind_group(1, camera_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.world_buffer.buffer.slice(..));
+        render_pass.draw(0..self.world_buffer.count, 0..1);
+    }
+}
+
+// two triangles per glyph, corners placed by position
+fn push_quads(vertices: &mut Vec<TextVertex>, layout: &TextLayout, color: [f32; 4], position: impl Fn([f32; 2]) -> Point3<f32>) {
+    for glyph in &layout.glyphs {
+        let corner = |x: usize, y: usize| {
+            let point = position([[glyph.min, glyph.max][x][0], [glyph.min, glyph.max][y][1]]);
+            TextVertex {
+                position: point.into(),
+                tex_coords: [[glyph.uv_min, glyph.uv_max][x][0], [glyph.uv_min, glyph.uv_max][y][1]],
+                color,
+            }
+        };
+        let (top_left, top_right, bottom_left, bottom_right) = (corner(0, 0), corner(1, 0), corner(0, 1), corner(1, 1));
+        vertices.extend_from_slice(&[top_left, bottom_left, top_right, top_right, bottom_left, bottom_right]);
+    }
+}
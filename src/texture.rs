@@ -1,6 +1,9 @@
-use std::io::Cursor;
+use std::cell::RefCell;
+use std::collections::HashMap;
 use image::GenericImageView;
 use anyhow::*;
+use bytemuck::{Pod, Zeroable};
+use wgpu::util::DeviceExt;
 use wgpu::{Device, Queue};
 
 pub struct Texture {
@@ -11,13 +14,21 @@ pub struct Texture {
 
 impl Texture {
     pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+    pub const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
 
-    pub fn from_bytes(device: &Device, queue: &Queue, bytes: &[u8], label: &str, is_normal_map: bool) -> Result<Self> {
+    // a full mip chain is generated when mipmaps is given
+    pub fn from_bytes(device: &Device, queue: &Queue, bytes: &[u8], label: &str, is_normal_map: bool, mipmaps: Option<&MipmapGenerator>) -> Result<Self> {
         let img = image::load_from_memory(bytes)?;
-        Self::from_image(device, queue, &img, Some(label), is_normal_map)
+        Self::from_image(device, queue, &img, Some(label), is_normal_map, mipmaps)
     }
 
-    pub fn from_image(device: &wgpu::Device, queue: &wgpu::Queue, image: &image::DynamicImage, label: Option<&str>, is_normal_map: bool) -> Result<Self> {
+    // 1x1 texture used in place of a missing material map
+    pub fn from_color(device: &Device, queue: &Queue, color: [u8; 4], label: &str, is_normal_map: bool) -> Result<Self> {
+        let img = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(1, 1, image::Rgba(color)));
+        Self::from_image(device, queue, &img, Some(label), is_normal_map, None)
+    }
+
+    pub fn from_image(device: &wgpu::Device, queue: &wgpu::Queue, image: &image::DynamicImage, label: Option<&str>, is_normal_map: bool, mipmaps: Option<&MipmapGenerator>) -> Result<Self> {
         let rgba = image.to_rgba8();
         let dimensions = image.dimensions();
 
@@ -34,14 +45,20 @@ impl Texture {
             wgpu::TextureFormat::Rgba8UnormSrgb
         };
 
+        let (mip_level_count, usage) = if mipmaps.is_some() {
+            (size.max_mips(wgpu::TextureDimension::D2), wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::RENDER_ATTACHMENT)
+        } else {
+            (1, wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST)
+        };
+
         let texture = device.create_texture(
             &wgpu::TextureDescriptor {
                 size,
-                mip_level_count: 1,
+                mip_level_count,
                 sample_count: 1,
                 dimension: wgpu::TextureDimension::D2,
                 format,
-                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                usage,
                 label,
                 view_formats: &[],
             }
@@ -63,21 +80,47 @@ impl Texture {
             size,
         );
 
+        if let (Some(mipmaps), true) = (mipmaps, mip_level_count > 1) {
+            mipmaps.generate(device, queue, &texture, format, mip_level_count);
+        }
+
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Linear,
-            min_filter: wgpu::FilterMode::Nearest,
-            mipmap_filter: wgpu::FilterMode::Nearest,
-            ..Default::default()
-        });
+        let sampler = if mipmaps.is_some() {
+            // trilinear, anisotropy gets clamped back to 1 by wgpu where it isn't supported
+            device.create_sampler(&wgpu::SamplerDescriptor {
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Linear,
+                anisotropy_clamp: 16,
+                ..Default::default()
+            })
+        } else {
+            device.create_sampler(&wgpu::SamplerDescriptor {
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Nearest,
+                mipmap_filter: wgpu::FilterMode::Nearest,
+                ..Default::default()
+            })
+        };
 
         Ok(Self { texture, view, sampler })
     }
 
-    pub fn create_depth_texture(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, label: &str) -> Self {
+    // swaps the default sampler, for formats like glTF that specify their own wrapping and filtering
+    pub fn with_sampler(self, device: &Device, descriptor: &wgpu::SamplerDescriptor) -> Self {
+        Self {
+            sampler: device.create_sampler(descriptor),
+            ..self
+        }
+    }
+
+    pub fn create_depth_texture(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, sample_count: u32, label: &str) -> Self {
         let size = if config.width == 0 || config.height == 0 {
             wgpu::Extent3d {
                 width: 1,
@@ -96,6 +139,100 @@ impl Texture {
             label: Some(label),
             size,
             mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format: Self::DEPTH_FORMAT,
+            // WebGL2 can't sample multisampled textures
+            usage: if sample_count > 1 {
+                wgpu::TextureUsages::RENDER_ATTACHMENT
+            } else {
+                wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING
+            },
+            view_formats: &[],
+        };
+        let texture = device.create_texture(&desc);
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(
+            &wgpu::SamplerDescriptor {
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Nearest,
+                compare: Some(wgpu::CompareFunction::LessEqual),
+                lod_min_clamp: 0.0,
+                lod_max_clamp: 100.0,
+                ..Default::default()
+            }
+        );
+
+        Self { texture, view, sampler }
+    }
+
+    // color target that gets resolved into the frame, only ever rendered to
+    pub fn create_multisampled_texture(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, format: wgpu::TextureFormat, sample_count: u32, label: &str) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: config.width.max(1),
+                height: config.height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor::default());
+
+        Self { texture, view, sampler }
+    }
+
+    // offscreen color target that later passes sample from, e.g. the HDR scene for post-processing
+    pub fn create_render_target(device: &wgpu::Device, width: u32, height: u32, format: wgpu::TextureFormat, label: &str) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: width.max(1),
+                height: height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Self { texture, view, sampler }
+    }
+
+    // one depth layer per shadow casting light, sampled with the same comparison sampler as the depth texture
+    pub fn create_shadow_map_array(device: &wgpu::Device, resolution: u32, layers: u32, label: &str) -> Self {
+        let desc = wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: resolution,
+                height: resolution,
+                depth_or_array_layers: layers,
+            },
+            mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
             format: Self::DEPTH_FORMAT,
@@ -104,7 +241,10 @@ impl Texture {
         };
         let texture = device.create_texture(&desc);
 
-        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
         let sampler = device.create_sampler(
             &wgpu::SamplerDescriptor {
                 address_mode_u: wgpu::AddressMode::ClampToEdge,
@@ -122,4 +262,466 @@ impl Texture {
 
         Self { texture, view, sampler }
     }
+
+    // HDR cubemap with faces in +X, -X, +Y, -Y, +Z, -Z order, filled by uploads or cubemap.wgsl passes
+    pub fn create_cubemap(device: &wgpu::Device, size: u32, mip_level_count: u32, label: &str) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: 6,
+            },
+            mip_level_count,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: Self::HDR_FORMAT,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::Cube),
+            ..Default::default()
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Self { texture, view, sampler }
+    }
+
+    // six square faces of the same size, mipmaps are rendered so HDR_FORMAT has to be renderable for them
+    pub fn cube_from_faces(device: &wgpu::Device, queue: &wgpu::Queue, faces: &[image::DynamicImage; 6], label: &str, mipmaps: Option<&CubemapRenderer>) -> Result<Self> {
+        let size = faces[0].width();
+        if faces.iter().any(|face| face.width() != size || face.height() != size) {
+            bail!("cubemap faces have to be square and all the same size");
+        }
+        let mip_level_count = if mipmaps.is_some() { Self::cube_mip_count(size) } else { 1 };
+        let cubemap = Self::create_cubemap(device, size, mip_level_count, label);
+
+        for (layer, face) in faces.iter().enumerate() {
+            let half_floats: Vec<u16> = face.to_rgba32f().into_raw().into_iter().map(f32_to_f16).collect();
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &cubemap.texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d { x: 0, y: 0, z: layer as u32 },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                bytemuck::cast_slice(&half_floats),
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(8 * size),
+                    rows_per_image: Some(size),
+                },
+                wgpu::Extent3d { width: size, height: size, depth_or_array_layers: 1 },
+            );
+        }
+
+        if let (Some(renderer), true) = (mipmaps, mip_level_count > 1) {
+            cubemap.generate_cube_mipmaps(device, queue, renderer);
+        }
+        Ok(cubemap)
+    }
+
+    // projects a latitude-longitude panorama, e.g. a decoded .hdr file, onto the faces on the GPU
+    pub fn cube_from_equirectangular(device: &wgpu::Device, queue: &wgpu::Queue, renderer: &CubemapRenderer, image: &image::DynamicImage, face_size: u32, label: &str, generate_mipmaps: bool) -> Result<Self> {
+        let (width, height) = image.dimensions();
+        let max_size = device.limits().max_texture_dimension_2d;
+        if width > max_size || height > max_size {
+            bail!("{}x{} panorama is larger than the {} texels the device supports", width, height, max_size);
+        }
+
+        let size = wgpu::Extent3d { width, height, depth_or_array_layers: 1 };
+        let half_floats: Vec<u16> = image.to_rgba32f().into_raw().into_iter().map(f32_to_f16).collect();
+        let panorama = device.create_texture_with_data(
+            queue,
+            &wgpu::TextureDescriptor {
+                label: Some("equirectangular texture"),
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: Self::HDR_FORMAT,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            },
+            wgpu::util::TextureDataOrder::LayerMajor,
+            bytemuck::cast_slice(&half_floats),
+        );
+        let panorama_view = panorama.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let mip_level_count = if generate_mipmaps { Self::cube_mip_count(face_size) } else { 1 };
+        let cubemap = Self::create_cubemap(device, face_size, mip_level_count, label);
+
+        renderer.render(device, queue, &cubemap, 0, CubePass::Equirectangular(&panorama_view));
+
+        if mip_level_count > 1 {
+            cubemap.generate_cube_mipmaps(device, queue, renderer);
+        }
+        Ok(cubemap)
+    }
+
+    fn cube_mip_count(size: u32) -> u32 {
+        u32::BITS - size.leading_zeros()
+    }
+
+    // same idea as generate_mipmaps, sampling the previous level of the cube in each texel's direction
+    fn generate_cube_mipmaps(&self, device: &wgpu::Device, queue: &wgpu::Queue, renderer: &CubemapRenderer) {
+        for mip in 1..self.texture.mip_level_count() {
+            let source = self.texture.create_view(&wgpu::TextureViewDescriptor {
+                label: Some("Cube mip view"),
+                dimension: Some(wgpu::TextureViewDimension::Cube),
+                base_mip_level: mip - 1,
+                mip_level_count: Some(1),
+                ..Default::default()
+            });
+            let source_size = (self.texture.width() >> (mip - 1)).max(1);
+            renderer.render(device, queue, self, mip, CubePass::Downsample(&source, source_size));
+        }
+    }
+}
+
+// a cubemap.wgsl entry point with what it samples from,
+// cube sources come with the face size of their top level
+#[derive(Copy, Clone)]
+pub enum CubePass<'a> {
+    Equirectangular(&'a wgpu::TextureView),
+    Downsample(&'a wgpu::TextureView, u32),
+    Irradiance(&'a wgpu::TextureView, u32),
+    // roughness of the mip level being rendered, 0 is a mirror
+    Prefilter(&'a wgpu::TextureView, u32, f32),
+}
+
+impl CubePass<'_> {
+    fn entry_point(&self) -> &'static str {
+        match self {
+            CubePass::Equirectangular(_) => "fs_equirectangular",
+            CubePass::Downsample(..) => "fs_downsample",
+            CubePass::Irradiance(..) => "fs_irradiance",
+            CubePass::Prefilter(..) => "fs_prefilter",
+        }
+    }
+}
+
+// Renders cube faces with cubemap.wgsl into HDR_FORMAT. The shader, sampler and face uniforms
+// are created once and reused for every mip level, pipelines once per fragment entry point.
+pub struct CubemapRenderer {
+    shader: wgpu::ShaderModule,
+    sampler: wgpu::Sampler,
+    // a slot per face, picked with a dynamic offset
+    uniform_buffer: wgpu::Buffer,
+    uniform_stride: u32,
+    equirectangular_layout: wgpu::BindGroupLayout,
+    cube_layout: wgpu::BindGroupLayout,
+    pipelines: RefCell<HashMap<&'static str, wgpu::RenderPipeline>>,
+}
+
+impl CubemapRenderer {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Cubemap Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/cubemap.wgsl").into()),
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Cubemap Sampler"),
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let uniform_stride = (std::mem::size_of::<CubeFaceUniform>() as u32)
+            .next_multiple_of(device.limits().min_uniform_buffer_offset_alignment);
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Cube Face Buffer"),
+            size: 6 * uniform_stride as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        // the source texture is only at the binding the entry point reads
+        let layout = |source_binding, view_dimension, label| device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: source_binding,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: true,
+                        min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<CubeFaceUniform>() as u64),
+                    },
+                    count: None,
+                },
+            ],
+            label: Some(label),
+        });
+
+        Self {
+            shader,
+            sampler,
+            uniform_buffer,
+            uniform_stride,
+            equirectangular_layout: layout(0, wgpu::TextureViewDimension::D2, "equirectangular bind group layout"),
+            cube_layout: layout(3, wgpu::TextureViewDimension::Cube, "cube bind group layout"),
+            pipelines: RefCell::new(HashMap::new()),
+        }
+    }
+
+    // renders every face of one mip level of target,
+    // submitted right away so the face uniforms can be rewritten for the next call
+    pub fn render(&self, device: &wgpu::Device, queue: &wgpu::Queue, target: &Texture, mip: u32, pass: CubePass) {
+        let (layout, source_binding, source_view, source_resolution, roughness) = match pass {
+            CubePass::Equirectangular(view) => (&self.equirectangular_layout, 0, view, 0, 0.0),
+            CubePass::Downsample(view, size) | CubePass::Irradiance(view, size) => (&self.cube_layout, 3, view, size, 0.0),
+            CubePass::Prefilter(view, size, roughness) => (&self.cube_layout, 3, view, size, roughness),
+        };
+
+        let mut uniforms = vec![0u8; 6 * self.uniform_stride as usize];
+        for face in 0..6 {
+            let uniform = CubeFaceUniform {
+                face,
+                roughness,
+                source_resolution: source_resolution as f32,
+                _padding: 0,
+            };
+            let offset = (face * self.uniform_stride) as usize;
+            uniforms[offset..offset + std::mem::size_of::<CubeFaceUniform>()].copy_from_slice(bytemuck::bytes_of(&uniform));
+        }
+        queue.write_buffer(&self.uniform_buffer, 0, &uniforms);
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: source_binding,
+                    resource: wgpu::BindingResource::TextureView(source_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer: &self.uniform_buffer,
+                        offset: 0,
+                        size: wgpu::BufferSize::new(std::mem::size_of::<CubeFaceUniform>() as u64),
+                    }),
+                },
+            ],
+            label: None,
+        });
+
+        let mut pipelines = self.pipelines.borrow_mut();
+        let entry_point = pass.entry_point();
+        let pipeline = pipelines.entry(entry_point).or_insert_with(|| {
+            let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Cubemap pipeline layout"),
+                bind_group_layouts: &[layout],
+                push_constant_ranges: &[],
+            });
+            crate::wgpu_helpers::RenderPipelineBuilder::new(&self.shader)
+                .label("Cubemap Pipeline")
+                .layout(&pipeline_layout)
+                .fragment_entry_point(Some(entry_point))
+                .color_target(Texture::HDR_FORMAT, crate::wgpu_helpers::BlendMode::Replace)
+                .cull_mode(None)
+                .build(device)
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Cubemap encoder")
+        });
+        for face in 0..6 {
+            let view = target.texture.create_view(&wgpu::TextureViewDescriptor {
+                label: Some("Cube face view"),
+                dimension: Some(wgpu::TextureViewDimension::D2),
+                base_mip_level: mip,
+                mip_level_count: Some(1),
+                base_array_layer: face,
+                array_layer_count: Some(1),
+                ..Default::default()
+            });
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Cube face pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_bind_group(0, &bind_group, &[face * self.uniform_stride]);
+            render_pass.draw(0..3, 0..1);
+        }
+        queue.submit(std::iter::once(encoder.finish()));
+    }
+}
+
+struct MipmapResources {
+    shader: wgpu::ShaderModule,
+    sampler: wgpu::Sampler,
+    pipelines: HashMap<wgpu::TextureFormat, wgpu::RenderPipeline>,
+}
+
+// The blit shader and sampler are created on first use and a pipeline once per texture format,
+// so loading textures doesn't compile a shader each time
+pub struct MipmapGenerator {
+    resources: RefCell<Option<MipmapResources>>,
+}
+
+impl Default for MipmapGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MipmapGenerator {
+    pub fn new() -> Self {
+        Self {
+            resources: RefCell::new(None),
+        }
+    }
+
+    // fills mip levels 1.. by repeatedly rendering the previous level at half size,
+    // copy based blits aren't available on WebGL
+    pub fn generate(&self, device: &wgpu::Device, queue: &wgpu::Queue, texture: &wgpu::Texture, format: wgpu::TextureFormat, mip_level_count: u32) {
+        let mut resources = self.resources.borrow_mut();
+        let resources = resources.get_or_insert_with(|| MipmapResources {
+            shader: device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Blit Shader"),
+                source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/blit.wgsl").into()),
+            }),
+            sampler: device.create_sampler(&wgpu::SamplerDescriptor {
+                label: Some("Mipmap Sampler"),
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Nearest,
+                ..Default::default()
+            }),
+            pipelines: HashMap::new(),
+        });
+        let MipmapResources { shader, sampler, pipelines } = resources;
+        let pipeline = pipelines.entry(format).or_insert_with(|| {
+            crate::wgpu_helpers::RenderPipelineBuilder::new(shader)
+                .label("Mipmap Pipeline")
+                .color_target(format, crate::wgpu_helpers::BlendMode::Replace)
+                .cull_mode(None)
+                .build(device)
+        });
+        let bind_group_layout = pipeline.get_bind_group_layout(0);
+
+        let views = (0..mip_level_count)
+            .map(|mip| texture.create_view(&wgpu::TextureViewDescriptor {
+                label: Some("Mip View"),
+                base_mip_level: mip,
+                mip_level_count: Some(1),
+                ..Default::default()
+            }))
+            .collect::<Vec<_>>();
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Mipmap encoder")
+        });
+        for target_mip in 1..mip_level_count as usize {
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&views[target_mip - 1]),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(sampler),
+                    },
+                ],
+                label: None,
+            });
+
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Mipmap pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &views[target_mip],
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_bind_group(0, &bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+        queue.submit(std::iter::once(encoder.finish()));
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+struct CubeFaceUniform {
+    face: u32,
+    roughness: f32,
+    source_resolution: f32,
+    _padding: u32,
+}
+
+// Rgba16Float uploads need half floats, rounded towards zero
+fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32 - 127 + 15;
+    let mantissa = bits & 0x7f_ffff;
+    if value.is_nan() {
+        return sign | 0x7e00;
+    }
+    if exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+    if exponent <= 0 {
+        // subnormal or too small to represent
+        if exponent < -10 {
+            return sign;
+        }
+        return sign | ((mantissa | 0x80_0000) >> (14 - exponent)) as u16;
+    }
+    sign | ((exponent as u16) << 10) | (mantissa >> 13) as u16
 }
\ No newline at end of file
@@ -0,0 +1,100 @@
+use instant::{Duration, Instant};
+
+// after a long stall the simulation slows down for a moment instead of freezing the frame with updates
+const MAX_STEPS_PER_FRAME: u32 = 8;
+
+#[derive(Debug, Copy, Clone)]
+pub struct TimingConfig {
+    // simulation steps per second, Runnable::update always gets 1 / update_rate
+    pub update_rate: f64,
+    // None leaves pacing to the display
+    pub max_frame_rate: Option<f64>,
+    // only redraw after input or while the page animates, and never while it's hidden
+    pub on_demand: bool,
+}
+
+impl Default for TimingConfig {
+    fn default() -> Self {
+        Self {
+            update_rate: 60.0,
+            max_frame_rate: None,
+            on_demand: true,
+        }
+    }
+}
+
+// Splits the time between frames into equally sized simulation steps
+pub struct FixedTimestep {
+    step: Duration,
+    accumulator: Duration,
+    last_time: Option<Instant>,
+}
+
+impl FixedTimestep {
+    pub fn new(update_rate: f64) -> Self {
+        Self {
+            step: Duration::from_secs_f64(1.0 / update_rate.max(1.0)),
+            accumulator: Duration::ZERO,
+            last_time: None,
+        }
+    }
+
+    pub fn step(&self) -> Duration {
+        self.step
+    }
+
+    // how many updates to run before rendering the frame at now, the first frame runs one
+    pub fn advance(&mut self, now: Instant) -> u32 {
+        let elapsed = match self.last_time {
+            Some(last_time) => now - last_time,
+            None => self.step,
+        };
+        self.last_time = Some(now);
+        self.accumulator += elapsed.min(self.step * MAX_STEPS_PER_FRAME);
+
+        let mut steps = 0;
+        while self.accumulator >= self.step {
+            self.accumulator -= self.step;
+            steps += 1;
+        }
+        steps
+    }
+
+    // how far the frame is between the last update and the next one
+    pub fn alpha(&self) -> f32 {
+        self.accumulator.as_secs_f32() / self.step.as_secs_f32()
+    }
+
+    // forgets time spent paused or loading so it isn't simulated all at once
+    pub fn reset(&mut self) {
+        self.accumulator = Duration::ZERO;
+        self.last_time = None;
+    }
+}
+
+// Holds frames back to the configured maximum rate
+pub struct FramePacer {
+    min_frame_time: Option<Duration>,
+    last_frame: Option<Instant>,
+}
+
+impl FramePacer {
+    pub fn new(max_frame_rate: Option<f64>) -> Self {
+        Self {
+            min_frame_time: max_frame_rate.map(|rate| Duration::from_secs_f64(1.0 / rate.max(1.0))),
+            last_frame: None,
+        }
+    }
+
+    // zero once the next frame may be drawn
+    pub fn time_until_next_frame(&self, now: Instant) -> Duration {
+        match (self.min_frame_time, self.last_frame) {
+            (Some(min_frame_time), Some(last_frame)) => (last_frame + min_frame_time).saturating_duration_since(now),
+            _ => Duration::ZERO,
+        }
+    }
+
+    pub fn frame_started(&mut self, now: Instant) {
+        self.last_frame = Some(now);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,59 @@
+use std::collections::HashMap;
+use winit::dpi::PhysicalPosition;
+use winit::event::{Touch, TouchPhase};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TouchGesture {
+    // single finger drag, in pixels
+    Drag { dx: f64, dy: f64 },
+    // change in distance between two fingers, in pixels
+    Pinch { delta: f64 },
+}
+
+#[derive(Debug, Default)]
+pub struct TouchTracker {
+    touches: HashMap<u64, PhysicalPosition<f64>>,
+}
+
+impl TouchTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn process_touch(&mut self, touch: &Touch) -> Option<TouchGesture> {
+        match touch.phase {
+            TouchPhase::Started => {
+                self.touches.insert(touch.id, touch.location);
+                None
+            }
+            TouchPhase::Ended | TouchPhase::Cancelled => {
+                self.touches.remove(&touch.id);
+                None
+            }
+            TouchPhase::Moved => {
+                let old_spread = self.spread();
+                let old_location = self.touches.insert(touch.id, touch.location)?;
+                match self.touches.len() {
+                    1 => Some(TouchGesture::Drag {
+                        dx: touch.location.x - old_location.x,
+                        dy: touch.location.y - old_location.y,
+                    }),
+                    2 => Some(TouchGesture::Pinch {
+                        delta: self.spread()? - old_spread?,
+                    }),
+                    _ => None
+                }
+            }
+        }
+    }
+
+    fn spread(&self) -> Option<f64> {
+        if self.touches.len() != 2 {
+            return None;
+        }
+        let mut locations = self.touches.values();
+        let a = locations.next()?;
+        let b = locations.next()?;
+        Some(((a.x - b.x).powi(2) + (a.y - b.y).powi(2)).sqrt())
+    }
+}
@@ -1,57 +1,175 @@
-pub fn create_render_pipeline(
-    device: &wgpu::Device,
-    layout: &wgpu::PipelineLayout,
-    color_format: wgpu::TextureFormat,
-    depth_format: Option<wgpu::TextureFormat>,
-    vertex_layouts: &[wgpu::VertexBufferLayout],
-    shader: wgpu::ShaderModuleDescriptor,
-) -> wgpu::RenderPipeline {
-    let shader = device.create_shader_module(shader);
-
-    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-        label: Some("Render Pipeline"),
-        layout: Some(layout),
-        vertex: wgpu::VertexState {
-            module: &shader,
-            entry_point: "vs_main",
-            buffers: vertex_layouts,
-            compilation_options: Default::default(),
-        },
-        fragment: Some(wgpu::FragmentState {
-            module: &shader,
-            entry_point: "fs_main",
-            targets: &[Some(wgpu::ColorTargetState {
-                format: color_format,
-                blend: Some(wgpu::BlendState {
-                    color: wgpu::BlendComponent::REPLACE,
-                    alpha: wgpu::BlendComponent::REPLACE,
-                }),
-                write_mask: wgpu::ColorWrites::ALL,
-            })],
-            compilation_options: Default::default(),
-        }),
-        primitive: wgpu::PrimitiveState {
-            topology: wgpu::PrimitiveTopology::TriangleList,
-            strip_index_format: None,
-            front_face: wgpu::FrontFace::Ccw,
-            cull_mode: Some(wgpu::Face::Back),
-            polygon_mode: wgpu::PolygonMode::Fill,
-            unclipped_depth: false,
-            conservative: false,
-        },
-        depth_stencil: depth_format.map(|format| wgpu::DepthStencilState {
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BlendMode {
+    Replace,
+    Alpha,
+    Premultiplied,
+    Additive,
+}
+
+impl BlendMode {
+    pub fn state(self) -> wgpu::BlendState {
+        match self {
+            BlendMode::Replace => wgpu::BlendState::REPLACE,
+            BlendMode::Alpha => wgpu::BlendState::ALPHA_BLENDING,
+            BlendMode::Premultiplied => wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING,
+            BlendMode::Additive => {
+                let add = wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::One,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                };
+                wgpu::BlendState {
+                    color: add,
+                    alpha: add,
+                }
+            }
+        }
+    }
+}
+
+// Defaults to vs_main/fs_main, back-face culling and no multisampling. There is no depth
+// attachment until .depth(..) is called. Without a layout wgpu derives one from the shader.
+pub struct RenderPipelineBuilder<'a> {
+    label: Option<&'a str>,
+    layout: Option<&'a wgpu::PipelineLayout>,
+    shader: &'a wgpu::ShaderModule,
+    vertex_entry_point: &'a str,
+    fragment_entry_point: Option<&'a str>,
+    vertex_layouts: &'a [wgpu::VertexBufferLayout<'a>],
+    color_targets: Vec<Option<wgpu::ColorTargetState>>,
+    depth_stencil: Option<wgpu::DepthStencilState>,
+    primitive: wgpu::PrimitiveState,
+    sample_count: u32,
+}
+
+impl<'a> RenderPipelineBuilder<'a> {
+    pub fn new(shader: &'a wgpu::ShaderModule) -> Self {
+        Self {
+            label: None,
+            layout: None,
+            shader,
+            vertex_entry_point: "vs_main",
+            fragment_entry_point: Some("fs_main"),
+            vertex_layouts: &[],
+            color_targets: Vec::new(),
+            depth_stencil: None,
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: Some(wgpu::Face::Back),
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            sample_count: 1,
+        }
+    }
+
+    pub fn label(mut self, label: &'a str) -> Self {
+        self.label = Some(label);
+        self
+    }
+
+    pub fn layout(mut self, layout: &'a wgpu::PipelineLayout) -> Self {
+        self.layout = Some(layout);
+        self
+    }
+
+    pub fn vertex_entry_point(mut self, entry_point: &'a str) -> Self {
+        self.vertex_entry_point = entry_point;
+        self
+    }
+
+    // None for depth only pipelines
+    pub fn fragment_entry_point(mut self, entry_point: Option<&'a str>) -> Self {
+        self.fragment_entry_point = entry_point;
+        self
+    }
+
+    pub fn vertex_layouts(mut self, vertex_layouts: &'a [wgpu::VertexBufferLayout<'a>]) -> Self {
+        self.vertex_layouts = vertex_layouts;
+        self
+    }
+
+    // adds the next color target, call once per @location output of the fragment shader
+    pub fn color_target(mut self, format: wgpu::TextureFormat, blend: BlendMode) -> Self {
+        self.color_targets.push(Some(wgpu::ColorTargetState {
             format,
-            depth_write_enabled: true,
-            depth_compare: wgpu::CompareFunction::Less,
+            blend: Some(blend.state()),
+            write_mask: wgpu::ColorWrites::ALL,
+        }));
+        self
+    }
+
+    pub fn depth(mut self, format: wgpu::TextureFormat, write_enabled: bool, compare: wgpu::CompareFunction) -> Self {
+        self.depth_stencil = Some(wgpu::DepthStencilState {
+            format,
+            depth_write_enabled: write_enabled,
+            depth_compare: compare,
             stencil: wgpu::StencilState::default(),
             bias: wgpu::DepthBiasState::default(),
-        }),
-        multisample: wgpu::MultisampleState {
-            count: 1,
-            mask: !0,
-            alpha_to_coverage_enabled: false,
-        },
-        multiview: None,
-    })
-}
+        });
+        self
+    }
+
+    // only has an effect after depth has been set
+    pub fn depth_bias(mut self, constant: i32, slope_scale: f32) -> Self {
+        if let Some(depth_stencil) = &mut self.depth_stencil {
+            depth_stencil.bias = wgpu::DepthBiasState {
+                constant,
+                slope_scale,
+                clamp: 0.0,
+            };
+        }
+        self
+    }
+
+    pub fn topology(mut self, topology: wgpu::PrimitiveTopology) -> Self {
+        self.primitive.topology = topology;
+        self
+    }
+
+    pub fn cull_mode(mut self, cull_mode: Option<wgpu::Face>) -> Self {
+        self.primitive.cull_mode = cull_mode;
+        self
+    }
+
+    // anything but Fill needs Features::POLYGON_MODE_LINE or POLYGON_MODE_POINT
+    pub fn polygon_mode(mut self, polygon_mode: wgpu::PolygonMode) -> Self {
+        self.primitive.polygon_mode = polygon_mode;
+        self
+    }
+
+    pub fn sample_count(mut self, sample_count: u32) -> Self {
+        self.sample_count = sample_count;
+        self
+    }
 
+    pub fn build(self, device: &wgpu::Device) -> wgpu::RenderPipeline {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(self.label.unwrap_or("Render Pipeline")),
+            layout: self.layout,
+            vertex: wgpu::VertexState {
+                module: self.shader,
+                entry_point: self.vertex_entry_point,
+                buffers: self.vertex_layouts,
+                compilation_options: Default::default(),
+            },
+            fragment: self.fragment_entry_point.map(|entry_point| wgpu::FragmentState {
+                module: self.shader,
+                entry_point,
+                targets: &self.color_targets,
+                compilation_options: Default::default(),
+            }),
+            primitive: self.primitive,
+            depth_stencil: self.depth_stencil,
+            multisample: wgpu::MultisampleState {
+                count: self.sample_count,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        })
+    }
+}
\ No newline at end of file
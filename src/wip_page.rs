@@ -1,90 +1,88 @@
+use crate::animation::{AnimationPlayer, Skin};
+use crate::morph::MorphTargets;
 use crate::camera;
+use crate::environment::Environment;
+use crate::frustum::{CullStats, Frustum};
+use crate::picking::{PickHit, Ray};
+use crate::light::{Light, LightsUniform};
+use crate::shadow::{ShadowConfig, ShadowMaps};
 use crate::grapics_context::GraphicsContext;
 use std::time::Duration;
-use bytemuck::{Pod, Zeroable};
-use cgmath::{Deg, One, Quaternion, Rotation3, Vector3};
+use cgmath::{Matrix4, Rotation3, Vector3};
 use wgpu::util::DeviceExt;
 use wgpu::SurfaceError;
-use winit::dpi::PhysicalSize;
-use winit::window::Window;
-use crate::model::{DrawModel, Instance, Vertex};
+use winit::dpi::PhysicalPosition;
+use winit::event::{DeviceEvent, ElementState, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent};
+use winit::keyboard::{KeyCode, PhysicalKey};
+use winit::window::CursorGrabMode;
+use crate::assets::Handle;
+use crate::model::{Instance, Model, Vertex};
+use crate::post_process::{PostProcess, PostProcessConfig};
+use crate::scene::{Attachment, NodeId, Scene};
+use crate::skybox::Skybox;
+use crate::text::{Font, TextAlign, TextRenderer, TextStyle, DEFAULT_CHARSET};
 use crate::texture::Texture;
+use crate::touch::{TouchGesture, TouchTracker};
+use crate::wgpu_helpers::{BlendMode, RenderPipelineBuilder};
 
-#[repr(C)]
-#[derive(Debug, Copy, Clone, Pod, Zeroable)]
-struct LightUniform {
-    position: [f32; 3],
-    _padding: f32,
-    color: [f32; 3],
-    _padding2: f32,
-}
+// samples per pixel while MSAA is on, lowered if the adapter can't do that many
+const MSAA_SAMPLES: u32 = 4;
+const HEADING_FONT: &str = "heading.ttf";
+
+// the old clear color, still used as the sky's zenith
+const SKY_COLOR: [f32; 3] = [0.012, 0.627, 1.0];
 
-pub struct WipPage<'a> {
-    graphics_context: GraphicsContext<'a>,
+pub struct WipPage {
+    shader: wgpu::ShaderModule,
+    render_pipeline_layout: wgpu::PipelineLayout,
     render_pipeline: wgpu::RenderPipeline,
-    camera: crate::camera::Camera,
+    sample_count: u32,
+    msaa_texture: Option<Texture>,
+    post_process: PostProcess,
+    camera: camera::OrbitCamera,
+    // the camera before the last update, frames are rendered in between the two
+    previous_camera: camera::OrbitCamera,
     projection: camera::Projection,
+    camera_controller: camera::OrbitCameraController,
+    drag_button: Option<MouseButton>,
+    cursor_position: PhysicalPosition<f64>,
+    touch_tracker: TouchTracker,
     camera_uniform: crate::camera::CameraUniform,
     camera_buffer: wgpu::Buffer,
+    camera_bind_group_layout: wgpu::BindGroupLayout,
     camera_bind_group: wgpu::BindGroup,
     depth_texture: Texture,
-    light_uniform: LightUniform,
+    light_uniform: LightsUniform,
     light_buffer: wgpu::Buffer,
     light_bind_group: wgpu::BindGroup,
-    obj_model: crate::model::Model,
-    instances: Vec<Instance>,
-    instance_buffer: wgpu::Buffer,
+    shadow_maps: ShadowMaps,
+    skybox: Skybox,
+    scene: Scene,
+    light_pivot: NodeId,
+    cull_stats: CullStats,
+    text: Option<TextRenderer>,
+    animated: Vec<AnimatedModel>,
+    // bound for meshes without morph targets
+    no_morph: MorphTargets,
 }
 
-impl<'a> crate::runnable::Runnable<'a> for WipPage<'a> {
-    async fn new(window: &'a Window) -> Self {
-        // graphics basics
-        let graphics_context = GraphicsContext::new(window).await;
+// all instances of an animated model share its pose and morph weights
+struct AnimatedModel {
+    model: usize,
+    player: AnimationPlayer,
+    // only for models with a skeleton
+    skin: Option<Skin>,
+}
 
-        // texture setup
-        let texture_bind_group_layout = graphics_context.device.create_bind_group_layout(
-            &wgpu::BindGroupLayoutDescriptor {
-                entries: &[
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Texture {
-                            multisampled: false,
-                            view_dimension: wgpu::TextureViewDimension::D2,
-                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 1,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 2,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Texture {
-                            multisampled: false,
-                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                            view_dimension: wgpu::TextureViewDimension::D2,
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 3,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                        count: None,
-                    },
-                ],
-                label: Some("texture_bind_group_layout"),
-            }
-        );
+impl WipPage {
+    // the model's materials have to be created with texture_bind_group_layout
+    pub fn with_model(graphics_context: &GraphicsContext<'_>, texture_bind_group_layout: &wgpu::BindGroupLayout, model: Handle<Model>) -> Self {
+        let config = graphics_context.config();
 
         // camera setup
-        let camera = camera::Camera::new((0.0, 1.0, 2.5), cgmath::Deg(-90.0), cgmath::Deg(-20.0));
-        let projection = camera::Projection::new(graphics_context.config.width, graphics_context.config.height, cgmath::Deg(45.0), 0.1, 100.0);
+        let camera = camera::OrbitCamera::new((0.0, 0.0, 0.0), 2.7, cgmath::Deg(90.0), cgmath::Deg(20.0), 1.0, 10.0);
+        let projection = camera::Projection::new(config.width, config.height, cgmath::Deg(45.0), 0.1, 100.0);
+        let camera_controller = camera::OrbitCameraController::new(0.08, 0.006, 0.02, 6.0);
 
         let mut camera_uniform = camera::CameraUniform::new();
         camera_uniform.update_view_proj(&camera, &projection);
@@ -108,29 +106,45 @@ impl<'a> crate::runnable::Runnable<'a> for WipPage<'a> {
                         min_binding_size: None,
                     },
                     count: None,
+                },
+                // joint matrices, every skinned model gets its own bind group sharing the camera buffer
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
                 }
             ],
             label: Some("camera bing group layout"),
         });
 
-        let camera_bind_group = graphics_context.device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &camera_bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: camera_buffer.as_entire_binding(),
-                }
-            ],
-            label: Some("camera bind group"),
-        });
+        // identity joints, for everything without a skeleton
+        let camera_bind_group = Skin::new(&graphics_context.device, &camera_bind_group_layout, &camera_buffer, "camera bind group").bind_group;
 
         // light setup
-        let light_uniform = LightUniform {
-            position: [-5.0, 0.0, -5.0],
-            _padding: 0.0,
-            color: [1.0, 1.0, 1.0],
-            _padding2: 0.0,
-        };
+        let mut scene = Scene::new();
+        let light_pivot = scene.add_node("light pivot", None, Instance::identity(), None);
+        let point_light = scene.add_light(Light::point((-5.0, 0.0, -5.0), (1.0, 1.0, 1.0), 50.0, 20.0));
+        scene.add_node("point light", Some(light_pivot), Instance::identity(), Some(Attachment::Light(point_light)));
+        let sun_direction = Vector3::new(-0.3, -1.0, -0.5);
+        let sun = scene.add_light(Light::directional(sun_direction, (1.0, 0.95, 0.9), 0.5).with_shadows());
+        scene.add_node("sun", None, Instance::identity(), Some(Attachment::Light(sun)));
+        scene.update(&graphics_context.device, &graphics_context.queue);
+
+        // sky and ambient light, a flat color where the maps can't be rendered
+        let environment = Environment::from_equirectangular(graphics_context, &sky_panorama(-sun_direction), 1.0)
+            .unwrap_or_else(|e| {
+                log::warn!("using a flat ambient color: {:#}", e);
+                Environment::uniform_color(graphics_context, SKY_COLOR, 0.3)
+            });
+
+        let shadow_maps = ShadowMaps::new(&graphics_context.device, &graphics_context.queue, ShadowConfig::default(), &camera_bind_group_layout);
+        let mut light_uniform = LightsUniform::new(scene.lights(), &shadow_maps.config);
+        light_uniform.set_environment(&environment);
 
         let light_buffer = graphics_context.device.create_buffer_init(
             &wgpu::util::BufferInitDescriptor {
@@ -152,7 +166,49 @@ impl<'a> crate::runnable::Runnable<'a> for WipPage<'a> {
                             min_binding_size: None,
                         },
                         count: None,
-                    }
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2Array,
+                            sample_type: wgpu::TextureSampleType::Depth,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::Cube,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 4,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::Cube,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 5,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
                 ],
                 label: None,
             }
@@ -164,99 +220,374 @@ impl<'a> crate::runnable::Runnable<'a> for WipPage<'a> {
                 wgpu::BindGroupEntry {
                     binding: 0,
                     resource: light_buffer.as_entire_binding(),
-                }
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&shadow_maps.texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&shadow_maps.texture.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(&environment.irradiance.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::TextureView(&environment.prefiltered.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: wgpu::BindingResource::Sampler(&environment.prefiltered.sampler),
+                },
             ],
             label: None,
         });
 
 
+        let morph_bind_group_layout = MorphTargets::create_bind_group_layout(&graphics_context.device);
+        let no_morph = MorphTargets::empty(&graphics_context.device, &graphics_context.queue, &morph_bind_group_layout);
+
         // render pipeline
         let render_pipeline_layout = graphics_context.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("Render pipeline layout"),
             bind_group_layouts: &[
-                &texture_bind_group_layout,
+                texture_bind_group_layout,
                 &camera_bind_group_layout,
-                &light_bind_group_layout
+                &light_bind_group_layout,
+                &morph_bind_group_layout
             ],
             push_constant_ranges: &[],
         });
-        let render_pipeline = {
-            let shader = wgpu::ShaderModuleDescriptor {
-                label: Some("Normal Shader"),
-                source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/wip.wgsl").into()),
-            };
-            crate::wgpu_helpers::create_render_pipeline(
-                &graphics_context.device,
-                &render_pipeline_layout,
-                graphics_context.config.format,
-                Some(Texture::DEPTH_FORMAT),
-                &[crate::model::ModelVertex::desc(), crate::model::InstanceRaw::desc()],
-                shader,
-            )
-        };
+        let shader = graphics_context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Normal Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/wip.wgsl").into()),
+        });
+        // the scene is drawn in HDR and tonemapped onto the frame afterwards
+        let post_process = PostProcess::new(graphics_context, PostProcessConfig::default());
+        let sample_count = graphics_context.sample_count(MSAA_SAMPLES, post_process.hdr_format(), Texture::DEPTH_FORMAT);
+        let render_pipeline = Self::create_render_pipeline(graphics_context, &render_pipeline_layout, &shader, &post_process, sample_count);
 
         // Depth texture
-        let depth_texture = Texture::create_depth_texture(&graphics_context.device, &graphics_context.config, "depth_texture");
+        let depth_texture = Texture::create_depth_texture(&graphics_context.device, &config, sample_count, "depth_texture");
+        let msaa_texture = Self::create_msaa_texture(graphics_context, &post_process, sample_count);
+        let skybox = Skybox::new(graphics_context, &environment, post_process.hdr_format(), sample_count);
+        skybox.update(&graphics_context.queue, &camera, &projection);
 
         // Model
-        let obj_model = crate::resources::load_model("WIP.obj", &graphics_context.device, &graphics_context.queue, &texture_bind_group_layout).await.unwrap();
-
-        // instances
-        let instances = vec![Instance{
-            position: Vector3{x: 0.0, y: 0.0, z: 0.0},
-            rotation: Quaternion::one()
-        }];
-        let instances_data = instances.iter().map(Instance::to_raw).collect::<Vec<_>>();
-        let instance_buffer = graphics_context.device.create_buffer_init(
-            &wgpu::util::BufferInitDescriptor {
-                label: Some("Instance Buffer"),
-                contents: bytemuck::cast_slice(&instances_data),
-                usage: wgpu::BufferUsages::VERTEX,
-            }
-        );
+        let model = scene.add_model(&graphics_context.device, model);
+        scene.add_node("WIP", None, Instance::identity(), Some(Attachment::Model(model)));
+        let animated = Self::create_animated(graphics_context, &scene, &camera_bind_group_layout, &camera_buffer);
 
 
         Self {
-            graphics_context,
+            shader,
+            render_pipeline_layout,
             render_pipeline,
+            sample_count,
+            msaa_texture,
+            post_process,
+            previous_camera: camera.clone(),
             camera,
             projection,
+            camera_controller,
+            drag_button: None,
+            touch_tracker: TouchTracker::new(),
             camera_uniform,
             camera_buffer,
+            camera_bind_group_layout,
             camera_bind_group,
             depth_texture,
             light_uniform,
             light_buffer,
             light_bind_group,
-            obj_model,
-            instances,
-            instance_buffer,
+            shadow_maps,
+            skybox,
+            scene,
+            light_pivot,
+            cull_stats: CullStats::default(),
+            cursor_position: PhysicalPosition::new(0.0, 0.0),
+            text: None,
+            animated,
+            no_morph,
         }
     }
 
-    fn update(&mut self, dt: Duration) {
-        let old_position: cgmath::Vector3<_> = self.light_uniform.position.into();
-        self.light_uniform.position =
-            (cgmath::Quaternion::from_axis_angle(Vector3::unit_x(), cgmath::Deg(60.0 * dt.as_secs_f32()))
-                * old_position)
-                .into();
-        self.graphics_context.queue.write_buffer(&self.light_buffer, 0, bytemuck::cast_slice(&[self.light_uniform]));
+    // plays the first clip of every model with a skeleton or morph targets
+    fn create_animated(graphics_context: &GraphicsContext, scene: &Scene, camera_bind_group_layout: &wgpu::BindGroupLayout, camera_buffer: &wgpu::Buffer) -> Vec<AnimatedModel> {
+        scene.models().iter()
+            .enumerate()
+            .filter(|(_, model)| model.skeleton.is_some() || model.meshes.iter().any(|mesh| mesh.morph_targets.is_some()))
+            .map(|(index, model)| {
+                let mut player = AnimationPlayer::new();
+                if !model.animations.is_empty() {
+                    player.play(0, true);
+                }
+                AnimatedModel {
+                    model: index,
+                    player,
+                    skin: model.skeleton.as_ref().map(|_| Skin::new(&graphics_context.device, camera_bind_group_layout, camera_buffer, "skinned camera bind group")),
+                }
+            })
+            .collect()
+    }
+
+    // skinned models are drawn with their own joints next to the camera
+    fn camera_bind_group_for(&self, model: usize) -> &wgpu::BindGroup {
+        self.animated.iter()
+            .find(|animated| animated.model == model)
+            .and_then(|animated| animated.skin.as_ref())
+            .map_or(&self.camera_bind_group, |skin| &skin.bind_group)
     }
 
-    fn render(&mut self) -> Result<(), SurfaceError> {
-        let output = self.graphics_context.surface.get_current_texture()?;
-        let view = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
+    // model is an index into the scene's models, None when it has neither a skeleton nor morph targets
+    pub fn animation_player(&mut self, model: usize) -> Option<&mut AnimationPlayer> {
+        self.animated.iter_mut()
+            .find(|animated| animated.model == model)
+            .map(|animated| &mut animated.player)
+    }
+
+    fn create_render_pipeline(graphics_context: &GraphicsContext, layout: &wgpu::PipelineLayout, shader: &wgpu::ShaderModule, post_process: &PostProcess, sample_count: u32) -> wgpu::RenderPipeline {
+        RenderPipelineBuilder::new(shader)
+            .layout(layout)
+            .vertex_layouts(&[crate::model::ModelVertex::desc(), crate::model::InstanceRaw::desc()])
+            .color_target(post_process.hdr_format(), BlendMode::Replace)
+            .depth(Texture::DEPTH_FORMAT, true, wgpu::CompareFunction::Less)
+            .sample_count(sample_count)
+            .build(&graphics_context.device)
+    }
+
+    // None without MSAA, the HDR target is rendered to directly then
+    fn create_msaa_texture(graphics_context: &GraphicsContext, post_process: &PostProcess, sample_count: u32) -> Option<Texture> {
+        (sample_count > 1).then(|| Texture::create_multisampled_texture(
+            &graphics_context.device,
+            &graphics_context.config(),
+            post_process.hdr_format(),
+            sample_count,
+            "msaa_texture",
+        ))
+    }
+
+    pub fn msaa_enabled(&self) -> bool {
+        self.sample_count > 1
+    }
+
+    // the pipeline and render targets have to be recreated for a different sample count
+    pub fn set_msaa(&mut self, graphics_context: &GraphicsContext, enabled: bool) {
+        let sample_count = graphics_context.sample_count(if enabled { MSAA_SAMPLES } else { 1 }, self.post_process.hdr_format(), Texture::DEPTH_FORMAT);
+        if sample_count == self.sample_count {
+            return;
+        }
+        log::info!("switching to {}x MSAA", sample_count);
+        self.sample_count = sample_count;
+        self.render_pipeline = Self::create_render_pipeline(graphics_context, &self.render_pipeline_layout, &self.shader, &self.post_process, sample_count);
+        self.skybox.set_sample_count(graphics_context, self.post_process.hdr_format(), sample_count);
+        if let Some(text) = &mut self.text {
+            text.set_sample_count(graphics_context, self.post_process.hdr_format(), sample_count);
+        }
+        crate::runnable::Runnable::resize(self, graphics_context);
+    }
+
+    // effects can be changed freely, they are applied on the next render
+    pub fn post_process_config(&mut self) -> &mut PostProcessConfig {
+        &mut self.post_process.config
+    }
 
-        let mut encoder = self.graphics_context.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+    // what's under a position in window pixels, as seen by the current camera
+    pub fn pick(&self, graphics_context: &GraphicsContext, position: PhysicalPosition<f64>) -> Option<PickHit> {
+        let ray = Ray::from_cursor(position, graphics_context.size(), &self.camera, &self.projection)?;
+        self.scene.pick(&ray)
+    }
+
+    // the page is drawn without its heading and labels until it has a font
+    pub fn set_font(&mut self, graphics_context: &GraphicsContext, font: Font) {
+        self.text = Some(TextRenderer::new(graphics_context, font, &self.camera_bind_group_layout, self.post_process.hdr_format(), self.sample_count));
+    }
+
+    // mesh instances drawn and skipped by the last frame
+    pub fn cull_stats(&self) -> CullStats {
+        self.cull_stats
+    }
+}
+
+impl crate::runnable::Runnable for WipPage {
+    async fn new(graphics_context: &GraphicsContext<'_>) -> Result<Self, crate::error::Error> {
+        // texture setup
+        let texture_bind_group_layout = crate::model::Material::create_bind_group_layout(&graphics_context.device);
+
+        let obj_model = graphics_context.assets.model("WIP.obj", &graphics_context.device, &graphics_context.queue, &texture_bind_group_layout).await
+            .map_err(|e| crate::error::Error::asset("WIP.obj", e))?;
+
+        let mut page = Self::with_model(graphics_context, &texture_bind_group_layout, obj_model);
+        match crate::resources::load_font(HEADING_FONT, DEFAULT_CHARSET, &graphics_context.device, &graphics_context.queue).await {
+            Ok(font) => page.set_font(graphics_context, font),
+            Err(e) => log::warn!("drawing without text: {:#}", e),
+        }
+        Ok(page)
+    }
+
+    fn input(&mut self, graphics_context: &GraphicsContext, event: &WindowEvent) -> bool {
+        match event {
+            // nothing changes on screen, so it's not consumed
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor_position = *position;
+                false
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                self.camera_controller.process_scroll(delta);
+                true
+            }
+            WindowEvent::MouseInput {
+                button: button @ (MouseButton::Left | MouseButton::Right),
+                state,
+                ..
+            } => {
+                if *button == MouseButton::Left && *state == ElementState::Pressed {
+                    if let Some(hit) = self.pick(graphics_context, self.cursor_position) {
+                        log::info!("clicked {:?}", hit);
+                    }
+                }
+                // lock the pointer while dragging so the camera can turn freely
+                // left button orbits, right button pans
+                self.drag_button = (*state == ElementState::Pressed).then_some(*button);
+                let grab_mode = if self.drag_button.is_some() { CursorGrabMode::Locked } else { CursorGrabMode::None };
+                if let Some(Err(e)) = graphics_context.window.map(|window| window.set_cursor_grab(grab_mode)) {
+                    log::warn!("could not change pointer lock: {:?}", e);
+                }
+                true
+            }
+            WindowEvent::KeyboardInput {
+                event: KeyEvent {
+                    physical_key: PhysicalKey::Code(KeyCode::KeyM),
+                    state: ElementState::Pressed,
+                    repeat: false,
+                    ..
+                },
+                ..
+            } => {
+                self.set_msaa(graphics_context, !self.msaa_enabled());
+                true
+            }
+            WindowEvent::KeyboardInput {
+                event: KeyEvent {
+                    physical_key: PhysicalKey::Code(key @ (KeyCode::KeyB | KeyCode::KeyF | KeyCode::KeyT | KeyCode::KeyV)),
+                    state: ElementState::Pressed,
+                    repeat: false,
+                    ..
+                },
+                ..
+            } => {
+                let config = self.post_process_config();
+                match key {
+                    KeyCode::KeyB => config.bloom = !config.bloom,
+                    KeyCode::KeyF => config.fxaa = !config.fxaa,
+                    KeyCode::KeyT => config.tonemapper = config.tonemapper.next(),
+                    _ => config.vignette = !config.vignette,
+                }
+                log::info!("post-processing: {:?}", config);
+                true
+            }
+            WindowEvent::Touch(touch) => {
+                match self.touch_tracker.process_touch(touch) {
+                    Some(TouchGesture::Drag { dx, dy }) => self.camera_controller.process_mouse(dx, dy),
+                    Some(TouchGesture::Pinch { delta }) => self.camera_controller.process_scroll(
+                        &MouseScrollDelta::PixelDelta(PhysicalPosition::new(0.0, -delta))
+                    ),
+                    None => {}
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn device_input(&mut self, _graphics_context: &GraphicsContext, event: &DeviceEvent) -> bool {
+        match event {
+            DeviceEvent::MouseMotion { delta } => match self.drag_button {
+                Some(MouseButton::Left) => {
+                    self.camera_controller.process_mouse(delta.0, delta.1);
+                    true
+                }
+                Some(MouseButton::Right) => {
+                    self.camera_controller.process_pan(delta.0, delta.1);
+                    true
+                }
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    fn update(&mut self, graphics_context: &GraphicsContext, dt: Duration) {
+        self.previous_camera = self.camera.clone();
+        self.camera_controller.update_camera(&mut self.camera, dt);
+
+        let light_pivot = self.scene.node_mut(self.light_pivot);
+        light_pivot.transform.rotation =
+            cgmath::Quaternion::from_axis_angle(Vector3::unit_x(), cgmath::Deg(60.0 * dt.as_secs_f32()))
+                * light_pivot.transform.rotation;
+        self.scene.update(&graphics_context.device, &graphics_context.queue);
+
+        for animated in &mut self.animated {
+            let model = &self.scene.models()[animated.model];
+            animated.player.update(dt, &model.animations);
+            if let (Some(skeleton), Some(skin)) = (&model.skeleton, &animated.skin) {
+                let pose = animated.player.pose(skeleton, &model.animations);
+                skin.update(&graphics_context.queue, &skeleton.joint_matrices(&pose));
+            }
+            // the meshes come from the primitives of one glTF mesh, which share its weights
+            for morph_targets in model.meshes.iter().filter_map(|mesh| mesh.morph_targets.as_ref()) {
+                let weights = animated.player.weights(&morph_targets.default_weights, &model.animations);
+                morph_targets.set_weights(&graphics_context.queue, &weights);
+            }
+        }
+
+        self.light_uniform.update(self.scene.lights(), &self.shadow_maps.config);
+        graphics_context.queue.write_buffer(&self.light_buffer, 0, bytemuck::cast_slice(&[self.light_uniform]));
+    }
+
+    fn render(&mut self, graphics_context: &GraphicsContext, alpha: f32) -> Result<(), SurfaceError> {
+        let camera = self.previous_camera.lerp(&self.camera, alpha);
+        self.camera_uniform.update_view_proj(&camera, &self.projection);
+        graphics_context.queue.write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&[self.camera_uniform]));
+        self.skybox.update(&graphics_context.queue, &camera, &self.projection);
+
+        if let Some(text) = &mut self.text {
+            text.queue_screen("Mateusz Hurnik", [24.0, 24.0], &TextStyle::new(48.0, [1.0; 4]));
+            let label = TextStyle::new(0.25, [1.0; 4]).with_align(TextAlign::Center);
+            let width = text.font().layout("WIP", &label).width;
+            text.queue_world("WIP", Matrix4::from_translation(Vector3::new(-width * 0.5, 0.9, 0.0)), &label);
+            text.prepare(graphics_context);
+        }
+
+        let frame = graphics_context.current_frame()?;
+
+        let mut encoder = graphics_context.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
             label: Some("Render encoder")
         });
 
+        self.shadow_maps.render(
+            &mut encoder,
+            &graphics_context.queue,
+            self.scene.lights(),
+            &self.scene.batches().into_iter()
+                .map(|(index, model, instance_buffer, instances)| (model, instance_buffer, instances, self.camera_bind_group_for(index)))
+                .collect::<Vec<_>>(),
+        );
+
+        // with MSAA the samples are resolved into the HDR target at the end of the pass
+        let (view, resolve_target, store) = match &self.msaa_texture {
+            Some(msaa_texture) => (&msaa_texture.view, Some(self.post_process.hdr_view()), wgpu::StoreOp::Discard),
+            None => (self.post_process.hdr_view(), None, wgpu::StoreOp::Store),
+        };
+
         {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Render pass"),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
+                    view,
+                    resolve_target,
                     ops: wgpu::Operations {
                         load: wgpu::LoadOp::Clear(wgpu::Color {
                             r: 0.012,
@@ -264,7 +595,7 @@ impl<'a> crate::runnable::Runnable<'a> for WipPage<'a> {
                             b: 1.0,
                             a: 1.0,
                         }),
-                        store: wgpu::StoreOp::Store,
+                        store,
                     },
                 })],
                 depth_stencil_attachment:  Some(wgpu::RenderPassDepthStencilAttachment {
@@ -281,30 +612,90 @@ impl<'a> crate::runnable::Runnable<'a> for WipPage<'a> {
 
 
             use crate::model::DrawModel;
-            render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
             render_pass.set_pipeline(&self.render_pipeline);
-            render_pass.draw_model_instanced(&self.obj_model, &self.camera_bind_group, &self.light_bind_group, 0..1);
+            // shadows still come from every instance, casters outside the view can shade what's in it
+            let frustum = Frustum::new(&camera, &self.projection);
+            for draw in self.scene.cull(&frustum, &mut self.cull_stats) {
+                render_pass.set_vertex_buffer(1, draw.instance_buffer.slice(..));
+                let material = &draw.model.materials[draw.mesh.material];
+                let camera_bind_group = self.camera_bind_group_for(draw.model_index);
+                let morph_targets = draw.mesh.morph_targets.as_ref().unwrap_or(&self.no_morph);
+                render_pass.set_bind_group(3, &morph_targets.bind_group, &[]);
+                for instances in draw.instances {
+                    render_pass.draw_mesh_instanced(draw.mesh, material, camera_bind_group, &self.light_bind_group, instances);
+                }
+            }
+            // last, so it's only shaded where the depth test shows nothing was drawn
+            self.skybox.render(&mut render_pass);
+            // blended, so after everything it can be in front of
+            if let Some(text) = &self.text {
+                text.render_world(&mut render_pass, &self.camera_bind_group);
+            }
+        }
+
+        self.post_process.render(graphics_context, &mut encoder, &frame.view);
+
+        // screen text goes on top of the finished frame, untouched by the post-processing
+        if let Some(text) = self.text.as_ref().filter(|text| text.has_screen_text()) {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Text pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &frame.view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            text.render_screen(&mut render_pass);
         }
 
-        self.graphics_context.queue.submit(std::iter::once(encoder.finish()));
-        output.present();
+        graphics_context.queue.submit(std::iter::once(encoder.finish()));
+        frame.present();
 
         Ok(())
     }
 
-    fn resize(&mut self, new_size: PhysicalSize<u32>) {
-        self.graphics_context.resize(new_size);
-        self.depth_texture = Texture::create_depth_texture(&self.graphics_context.device, &self.graphics_context.config, "depth_texture");
-        self.projection.resize(self.graphics_context.config.width, self.graphics_context.config.height);
+    fn resize(&mut self, graphics_context: &GraphicsContext) {
+        let config = graphics_context.config();
+        self.depth_texture = Texture::create_depth_texture(&graphics_context.device, &config, self.sample_count, "depth_texture");
+        self.msaa_texture = Self::create_msaa_texture(graphics_context, &self.post_process, self.sample_count);
+        self.post_process.resize(graphics_context);
+        self.projection.resize(config.width, config.height);
         self.camera_uniform.update_view_proj(&self.camera, &self.projection);
-        self.graphics_context.queue.write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&[self.camera_uniform]));
+        graphics_context.queue.write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&[self.camera_uniform]));
+        self.skybox.update(&graphics_context.queue, &self.camera, &self.projection);
     }
+}
 
-    fn get_size(&self) -> PhysicalSize<u32> {
-        self.graphics_context.size
-    }
+// a simple gradient sky with a sun disc, stands in for an .hdr panorama
+fn sky_panorama(sun_direction: Vector3<f32>) -> image::DynamicImage {
+    use cgmath::InnerSpace;
+    let (width, height) = (512, 256);
+    let sun_direction = sun_direction.normalize();
+    let zenith = Vector3::from(SKY_COLOR);
+    let horizon = Vector3::new(0.35, 0.8, 1.0);
+    let ground = Vector3::new(0.05, 0.3, 0.5);
 
-    fn window(&self) -> &Window {
-        self.graphics_context.window
-    }
+    let image = image::Rgba32FImage::from_fn(width, height, |x, y| {
+        // the inverse of the lookup in cubemap.wgsl
+        let phi = ((x as f32 + 0.5) / width as f32 - 0.5) * std::f32::consts::TAU;
+        let theta = (y as f32 + 0.5) / height as f32 * std::f32::consts::PI;
+        let direction = Vector3::new(theta.sin() * phi.cos(), theta.cos(), theta.sin() * phi.sin());
+
+        let mut color = if direction.y >= 0.0 {
+            horizon + (zenith - horizon) * direction.y.sqrt()
+        } else {
+            horizon + (ground - horizon) * (-direction.y * 4.0).min(1.0)
+        };
+        if direction.dot(sun_direction) > 3.0f32.to_radians().cos() {
+            color += Vector3::new(40.0, 38.0, 36.0);
+        }
+        image::Rgba([color.x, color.y, color.z, 1.0])
+    });
+    image::DynamicImage::ImageRgba32F(image)
 }
\ No newline at end of file
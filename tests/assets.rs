@@ -0,0 +1,50 @@
+#![cfg(not(target_arch = "wasm32"))]
+
+use std::rc::Rc;
+use base64::Engine;
+use personal_page::assets::AssetManager;
+use personal_page::grapics_context::GraphicsContext;
+use personal_page::model::Material;
+use winit::dpi::PhysicalSize;
+
+fn headless_context() -> GraphicsContext<'static> {
+    pollster::block_on(GraphicsContext::new_headless(PhysicalSize::new(1, 1), true, Some(wgpu::Limits::downlevel_webgl2_defaults())))
+        .unwrap_or_else(|e| panic!("asset tests need a software adapter (llvmpipe or lavapipe): {}", e))
+}
+
+// a single triangle with its buffer inlined, so the file is all there is to load
+fn write_triangle_gltf(name: &str) -> std::path::PathBuf {
+    let positions: [[f32; 3]; 3] = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]];
+    let buffer = base64::engine::general_purpose::STANDARD.encode(bytemuck::cast_slice(&positions));
+    let json = format!(r#"{{
+        "asset": {{ "version": "2.0" }},
+        "buffers": [{{ "byteLength": 36, "uri": "data:application/octet-stream;base64,{}" }}],
+        "bufferViews": [{{ "buffer": 0, "byteLength": 36 }}],
+        "accessors": [{{ "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3", "min": [0, 0, 0], "max": [1, 1, 0] }}],
+        "meshes": [{{ "primitives": [{{ "attributes": {{ "POSITION": 0 }} }}] }}],
+        "nodes": [{{ "mesh": 0 }}],
+        "scenes": [{{ "nodes": [0] }}]
+    }}"#, buffer);
+    let path = std::env::temp_dir().join(format!("{}-{}.gltf", name, std::process::id()));
+    std::fs::write(&path, json).unwrap();
+    path
+}
+
+#[test]
+fn loading_a_gltf_twice_shares_it() {
+    let graphics_context = headless_context();
+    let device = &graphics_context.device;
+    let queue = &graphics_context.queue;
+    let layout = Material::create_bind_group_layout(device);
+    let assets = AssetManager::new();
+    let path = write_triangle_gltf("shared");
+    // the native build joins asset names onto its assets directory, an absolute path replaces it
+    let file_name = path.to_str().unwrap();
+
+    let first = pollster::block_on(assets.gltf(file_name, device, queue, &layout)).unwrap();
+    let second = pollster::block_on(assets.gltf(file_name, device, queue, &layout)).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(Rc::ptr_eq(&first, &second));
+    assert_eq!(first.len(), 1);
+}
\ No newline at end of file
@@ -0,0 +1,88 @@
+#![cfg(not(target_arch = "wasm32"))]
+
+use std::rc::Rc;
+use personal_page::grapics_context::GraphicsContext;
+use personal_page::headless;
+use personal_page::model::{Material, MaterialTextures, MaterialUniform, Mesh, Model, ModelVertex};
+use personal_page::texture::Texture;
+use personal_page::wip_page::WipPage;
+use winit::dpi::PhysicalSize;
+
+// Run with UPDATE_GOLDEN=1 to regenerate the images in tests/golden after an intended visual change.
+// Uses a software adapter (llvmpipe, lavapipe) so the output is the same on every machine,
+// without one the tests fail rather than pass without rendering anything.
+// The device only gets WebGL2's limits, so going over them fails here before it fails in a browser.
+
+fn headless_context() -> GraphicsContext<'static> {
+    pollster::block_on(GraphicsContext::new_headless(PhysicalSize::new(256, 256), true, Some(wgpu::Limits::downlevel_webgl2_defaults())))
+        .unwrap_or_else(|e| panic!("golden image tests need a software adapter (llvmpipe or lavapipe): {}", e))
+}
+
+fn cube(graphics_context: &GraphicsContext, layout: &wgpu::BindGroupLayout) -> Model {
+    let device = &graphics_context.device;
+    let queue = &graphics_context.queue;
+
+    // normal, tangent and bitangent of every face
+    let faces: [([f32; 3], [f32; 3], [f32; 3]); 6] = [
+        ([1.0, 0.0, 0.0], [0.0, 0.0, -1.0], [0.0, 1.0, 0.0]),
+        ([-1.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, 1.0, 0.0]),
+        ([0.0, 1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, -1.0]),
+        ([0.0, -1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]),
+        ([0.0, 0.0, 1.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+        ([0.0, 0.0, -1.0], [-1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+    ];
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+    for (normal, tangent, bitangent) in faces {
+        let first = vertices.len() as u32;
+        for (u, v) in [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)] {
+            let (s, t) = (u * 2.0 - 1.0, v * 2.0 - 1.0);
+            let position = [0, 1, 2].map(|i| (normal[i] + tangent[i] * s + bitangent[i] * t) * 0.5);
+            vertices.push(ModelVertex {
+                position,
+                tex_coords: [u, 1.0 - v],
+                normal,
+                tangent,
+                bitangent,
+                joints: [0; 4],
+                weights: [0.0; 4],
+            });
+        }
+        indices.extend([first, first + 1, first + 2, first, first + 2, first + 3]);
+    }
+
+    let textures = MaterialTextures {
+        base_color: Rc::new(Texture::from_color(device, queue, [255; 4], "base_color", false).unwrap()),
+        normal: Rc::new(Texture::from_color(device, queue, [128, 128, 255, 255], "normal", true).unwrap()),
+        metallic_roughness: Rc::new(Texture::from_color(device, queue, [255; 4], "metallic_roughness", true).unwrap()),
+        occlusion: Rc::new(Texture::from_color(device, queue, [255; 4], "occlusion", true).unwrap()),
+        emissive: Rc::new(Texture::from_color(device, queue, [255; 4], "emissive", false).unwrap()),
+    };
+    let uniform = MaterialUniform {
+        base_color_factor: [0.8, 0.3, 0.2, 1.0],
+        emissive_factor: [0.0; 3],
+        metallic_factor: 0.0,
+        roughness_factor: 0.5,
+        occlusion_strength: 1.0,
+        normal_scale: 1.0,
+        _padding: 0.0,
+    };
+
+    Model {
+        meshes: vec![Mesh::new(device, "cube", &vertices, &indices, 0)],
+        materials: vec![Material::new(device, "cube", textures, uniform, layout)],
+        skeleton: None,
+        animations: Vec::new(),
+    }
+}
+
+#[test]
+fn wip_page_cube() {
+    let graphics_context = headless_context();
+    let layout = Material::create_bind_group_layout(&graphics_context.device);
+    let model = cube(&graphics_context, &layout);
+    let mut page = WipPage::with_model(&graphics_context, &layout, Rc::new(model));
+
+    let frame = headless::render_page(&graphics_context, &mut page, 1, instant::Duration::ZERO).unwrap();
+    headless::assert_matches_reference(&frame, "tests/golden/wip_page_cube.png", 2, 0.001).unwrap();
+}
\ No newline at end of file